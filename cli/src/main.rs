@@ -1 +1,119 @@
-fn main() {}
+use brainfsym::ExploreConfig;
+use brainfsym::Session;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "\
+usage: brainfsym-cli gen-tests <program.bf> <out-dir> [options]
+       brainfsym-cli dict <program.bf> <out.dict> [options]
+
+gen-tests: explore every path of the program and write one input file per
+exited path to <out-dir>, each with a JSON sidecar of its expected output
+and branches.
+
+dict: explore every path of the program and write the values input bytes
+are compared against to <out.dict>, as a dictionary for AFL's -x option.
+
+options:
+    --mem-size <cells>      number of memory cells on the tape
+    --max-input-len <len>   maximum number of input bytes
+    --max-steps <steps>     maximum number of instructions per path
+    --max-states <states>   stop after exploring this many states
+    --timeout <seconds>     stop after this much time";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let res = match args.first().map(String::as_str) {
+        Some("gen-tests") => gen_tests(&args[1..]),
+        Some("dict") => dict(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn gen_tests(args: &[String]) -> Result<(), String> {
+    let (paths, config) = parse_options(args)?;
+    let (prog_path, out_dir) = match paths.as_slice() {
+        [prog_path, out_dir] => (prog_path, out_dir),
+        _ => return Err(USAGE.to_string()),
+    };
+    let source = fs::read_to_string(prog_path)
+        .map_err(|err| format!("failed reading {}: {}", prog_path, err))?;
+    let mut session = Session::from_str(&source, config).map_err(|err| err.to_string())?;
+    let suite = session.generate_tests().map_err(|err| err.to_string())?;
+    suite
+        .write_to(Path::new(out_dir))
+        .map_err(|err| format!("failed writing {}: {}", out_dir, err))?;
+    eprintln!(
+        "wrote {} test cases to {}{}",
+        suite.cases.len(),
+        out_dir,
+        if suite.complete {
+            ""
+        } else {
+            " (budget ran out before every path was explored)"
+        }
+    );
+    Ok(())
+}
+
+fn dict(args: &[String]) -> Result<(), String> {
+    let (paths, config) = parse_options(args)?;
+    let (prog_path, out_path) = match paths.as_slice() {
+        [prog_path, out_path] => (prog_path, out_path),
+        _ => return Err(USAGE.to_string()),
+    };
+    let source = fs::read_to_string(prog_path)
+        .map_err(|err| format!("failed reading {}: {}", prog_path, err))?;
+    let mut session = Session::from_str(&source, config).map_err(|err| err.to_string())?;
+    let dict = session
+        .extract_dictionary()
+        .map_err(|err| err.to_string())?;
+    dict.write_to(Path::new(out_path))
+        .map_err(|err| format!("failed writing {}: {}", out_path, err))?;
+    eprintln!(
+        "wrote {} tokens to {}{}",
+        dict.tokens.len(),
+        out_path,
+        if dict.complete {
+            ""
+        } else {
+            " (budget ran out before every path was explored)"
+        }
+    );
+    Ok(())
+}
+
+/// Split `args` into positional arguments and the exploration options
+fn parse_options(args: &[String]) -> Result<(Vec<String>, ExploreConfig), String> {
+    let mut paths = vec![];
+    let mut config = ExploreConfig::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            paths.push(arg.clone());
+            continue;
+        }
+        let val = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", arg))?
+            .parse::<usize>()
+            .map_err(|err| format!("invalid value for {}: {}", arg, err))?;
+        config = match arg.as_str() {
+            "--mem-size" => config.mem_size(val),
+            "--max-input-len" => config.max_input_len(Some(val)),
+            "--max-steps" => config.max_steps(Some(val)),
+            "--max-states" => config.max_states(Some(val)),
+            "--timeout" => config.timeout(Some(Duration::from_secs(val as u64))),
+            _ => return Err(format!("unknown option {}\n\n{}", arg, USAGE)),
+        };
+    }
+    Ok((paths, config))
+}
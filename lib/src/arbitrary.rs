@@ -0,0 +1,221 @@
+use derive_setters::Setters;
+
+/// Shape of randomly generated programs. Programs are built from a stream of
+/// choice bytes, so shrinking the bytes toward zero shrinks the program.
+#[derive(Clone, Setters, PartialEq, Eq, Debug, Hash)]
+pub struct ProgParams {
+    /// Approximate maximum number of instructions. Moves back to a loop's
+    /// starting cell can go slightly past it.
+    pub max_len: usize,
+
+    /// Maximum nesting depth of loops
+    pub max_loop_depth: usize,
+
+    /// Maximum distance of the data pointer from where it started. Loops
+    /// move back to the cell they started at, so the distance is static.
+    pub max_offset: Option<usize>,
+
+    /// Only generate counter loops, which decrement the cell they started at
+    /// once per iteration and don't otherwise touch it. Every program then
+    /// terminates, as long as the tape has at least
+    /// `PointerAnalysis::min_tape_size` cells.
+    pub terminating: bool,
+}
+
+impl Default for ProgParams {
+    fn default() -> Self {
+        Self {
+            max_len: 32,
+            max_loop_depth: 2,
+            max_offset: None,
+            terminating: false,
+        }
+    }
+}
+
+impl ProgParams {
+    /// Balanced source decided by `choices`. The program ends early at a 0
+    /// byte outside a loop, or when `choices` runs out.
+    pub fn build(&self, choices: &[u8]) -> String {
+        let mut builder = Builder {
+            params: self,
+            choices: choices.iter(),
+            source: String::new(),
+            offset: 0,
+            counters: vec![],
+        };
+        builder.block(self.max_loop_depth);
+        builder.source
+    }
+}
+
+struct Builder<'a> {
+    params: &'a ProgParams,
+    choices: std::slice::Iter<'a, u8>,
+    source: String,
+
+    /// Static data pointer offset, if loops are balanced
+    offset: isize,
+
+    /// Offsets of the enclosing counter loops' cells
+    counters: Vec<isize>,
+}
+
+impl Builder<'_> {
+    /// Whether loops have to leave the pointer where they found it
+    fn balanced(&self) -> bool {
+        self.params.terminating || self.params.max_offset.is_some()
+    }
+
+    fn block(&mut self, depth: usize) {
+        while self.source.len() < self.params.max_len {
+            let choice = match self.choices.next() {
+                Some(choice) => choice,
+                None => return,
+            };
+            match choice % 8 {
+                0 => return,
+                1 => self.cell_op('+'),
+                2 => self.cell_op('-'),
+                3 => self.source.push('.'),
+                4 => self.cell_op(','),
+                5 => self.shift(1),
+                6 => self.shift(-1),
+                _ => self.loop_(depth),
+            }
+        }
+    }
+
+    /// Push an instruction changing the cell, unless it's a counter
+    fn cell_op(&mut self, insn: char) {
+        if !self.counters.contains(&self.offset) {
+            self.source.push(insn);
+        }
+    }
+
+    fn shift(&mut self, delta: isize) {
+        let offset = self.offset + delta;
+        if offset.unsigned_abs() <= self.params.max_offset.unwrap_or(usize::MAX) {
+            self.source.push(if delta > 0 { '>' } else { '<' });
+            self.offset = offset;
+        }
+    }
+
+    fn loop_(&mut self, depth: usize) {
+        if depth == 0 || self.counters.contains(&self.offset) {
+            return;
+        }
+        let start = self.offset;
+        self.source.push('[');
+        if self.params.terminating {
+            self.source.push('-');
+            self.counters.push(start);
+        }
+        self.block(depth - 1);
+        if self.balanced() {
+            while self.offset != start {
+                let delta = if self.offset < start { 1 } else { -1 };
+                self.source.push(if delta > 0 { '>' } else { '<' });
+                self.offset += delta;
+            }
+        }
+        if self.params.terminating {
+            self.counters.pop();
+        }
+        self.source.push(']');
+    }
+}
+
+/// Xorshift generator, so random programs and inputs are reproducible from a
+/// seed without a dependency
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at zero
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod strategy {
+    use super::*;
+    use crate::ast;
+
+    use proptest::prelude::*;
+
+    /// Sources of balanced programs shaped by `params`
+    pub fn prog_source(params: ProgParams) -> impl Strategy<Value = String> {
+        proptest::collection::vec(any::<u8>(), 0..=params.max_len * 2)
+            .prop_map(move |choices| params.build(&choices))
+    }
+
+    impl Arbitrary for ast::Prog {
+        type Parameters = ProgParams;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(params: ProgParams) -> Self::Strategy {
+            prog_source(params)
+                .prop_map(|source| {
+                    ast::Prog::from_str(&source).expect("generated program is unbalanced")
+                })
+                .boxed()
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+pub use strategy::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+    use crate::pointer::PointerAnalysis;
+
+    #[test]
+    fn test_build() {
+        let params = ProgParams::default();
+        assert_eq!(params.build(&[1, 7, 2, 5, 0, 3]), "+[->].");
+        assert_eq!(params.build(&[1, 0, 3]), "+");
+
+        // Loops move back to their cell, and counters are left alone
+        let params = params.max_offset(Some(1)).terminating(true);
+        assert_eq!(params.build(&[4, 7, 5, 5, 2, 0, 3]), ",[->-<].");
+        assert_eq!(params.build(&[4, 7, 5, 1, 6, 2, 4]), ",[->+<]");
+        let prog = ast::Prog::from_str(&params.build(&[4, 7, 5, 1, 6, 2, 4])).unwrap();
+        assert_eq!(PointerAnalysis::new(&prog).offset_range(), Some(0..=1));
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+        #[test]
+        fn test_terminating(
+            prog in proptest::arbitrary::any_with::<ast::Prog>(
+                ProgParams::default().max_offset(Some(2)).terminating(true)
+            )
+        ) {
+            use crate::config::ExploreConfig;
+            use crate::interp::InterpStop;
+            use crate::interp::Interpreter;
+
+            let offsets = PointerAnalysis::new(&prog).offset_range().unwrap();
+            proptest::prop_assert!(*offsets.start() >= -2 && *offsets.end() <= 2);
+
+            let config = ExploreConfig::default().max_steps(Some(10_000_000));
+            let mut interp = Interpreter::new(std::rc::Rc::new(prog), std::rc::Rc::new(config), vec![]).unwrap();
+            proptest::prop_assert_eq!(interp.run(), Ok(InterpStop::Exited));
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use crate::error::Error;
+use crate::error::Result;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,13 +100,27 @@ pub enum Insn {
     JmpIfNonZero(usize),
 }
 
-pub type ParseError<'a> = nom::Err<nom::types::CompleteStr<'a>>;
-pub type ParseResult<'a> = Result<Prog, ParseError<'a>>;
+impl Insn {
+    /// Brainf*** character of this instruction
+    pub fn to_char(&self) -> char {
+        match self {
+            Insn::Right => '>',
+            Insn::Left => '<',
+            Insn::Inc => '+',
+            Insn::Dec => '-',
+            Insn::Out => '.',
+            Insn::In => ',',
+            Insn::JmpIfZero(_) => '[',
+            Insn::JmpIfNonZero(_) => ']',
+        }
+    }
+}
 
 impl Prog {
-    pub fn from_str(s: &str) -> ParseResult {
+    pub fn from_str(s: &str) -> Result<Self> {
         brainfuck::parser::parse(nom::types::CompleteStr(s))
             .map(|prog| Self::from_brainf_block(0, &prog))
+            .map_err(|err| Error::Parse(format!("{:?}", err)))
     }
 
     fn from_brainf_block(insn_ptr: usize, block: &brainfuck::ast::Block) -> Self {
@@ -0,0 +1,15 @@
+//! Runs the brainf*** program given as the only argument with the
+//! `brainfuck_rs` interpreter, reading stdin and writing stdout. This is the
+//! reference `difftest()` compares the symbolic engine against.
+
+use std::env;
+use std::io;
+use std::io::Write;
+
+fn main() {
+    let source = env::args().nth(1).expect("usage: bf-reference <program>");
+    let block = brainfuck::parser::parse(nom::types::CompleteStr(&source))
+        .expect("failed to parse the program");
+    brainfuck::context::Context::new().run(&block);
+    io::stdout().flush().expect("failed to write the output");
+}
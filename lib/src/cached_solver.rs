@@ -1,14 +1,19 @@
+use crate::error::Error;
+use crate::observer::ObserverRef;
+use crate::stats::SolverStats;
+
 use std::collections::HashMap;
+use std::mem;
 use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
 
 pub struct CachedSolver<'ctx> {
     cache: HashMap<z3::ast::Bool<'ctx>, SolverResultModel<'ctx>>,
-}
+    stats: SolverStats,
 
-#[derive(Clone)]
-pub enum Error {
-    Unsat,
-    Unknown,
+    /// Observers of the path group currently exploring with this solver
+    observers: Vec<ObserverRef<'ctx>>,
 }
 
 pub type SolverResult<T> = Result<T, Error>;
@@ -19,25 +24,82 @@ impl<'ctx> CachedSolver<'ctx> {
     pub fn new() -> Self {
         Self {
             cache: HashMap::new(),
+            stats: SolverStats::default(),
+            observers: vec![],
         }
     }
 
+    /// Replace the observers notified of solver queries, returning the old ones
+    pub(crate) fn swap_observers(
+        &mut self,
+        observers: Vec<ObserverRef<'ctx>>,
+    ) -> Vec<ObserverRef<'ctx>> {
+        mem::replace(&mut self.observers, observers)
+    }
+
+    pub fn stats(&self) -> SolverStats {
+        self.stats
+    }
+
+    /// Number of queries that were sent to z3 instead of answered from the
+    /// cache
+    pub fn solver_calls(&self) -> usize {
+        self.stats.solver_calls()
+    }
+
     pub fn solve(
         &mut self,
         ctx: &'ctx z3::Context,
         expr: z3::ast::Bool<'ctx>,
     ) -> SolverResultModel {
-        match self.cache.get(&expr) {
-            Some(res) => res.clone(),
+        self.solve_within(ctx, expr, None)
+    }
+
+    /// Like `solve()`, but z3 gives up with `Error::Unknown` after `timeout`
+    pub fn solve_within(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        expr: z3::ast::Bool<'ctx>,
+        timeout: Option<Duration>,
+    ) -> SolverResultModel {
+        for observer in &self.observers {
+            observer.borrow_mut().on_solver_query(&expr);
+        }
+        let res = self.solve_helper(ctx, &expr, timeout);
+        for observer in &self.observers {
+            observer.borrow_mut().on_solver_result(&expr, &res);
+        }
+        res
+    }
+
+    fn solve_helper(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        expr: &z3::ast::Bool<'ctx>,
+        timeout: Option<Duration>,
+    ) -> SolverResultModel<'ctx> {
+        self.stats.queries += 1;
+        match self.cache.get(expr) {
+            Some(res) => {
+                self.stats.cache_hits += 1;
+                res.clone()
+            }
             None => {
+                let start = Instant::now();
                 let solver = z3::Solver::new(ctx);
-                solver.assert(&expr);
+                if let Some(timeout) = timeout {
+                    let mut params = z3::Params::new(ctx);
+                    params.set_u32("timeout", timeout.as_millis().min(u32::MAX as u128) as u32);
+                    solver.set_params(&params);
+                }
+                solver.assert(expr);
                 let res = match solver.check() {
                     z3::SatResult::Sat => Ok(Rc::new(solver.get_model())),
                     z3::SatResult::Unsat => Err(Error::Unsat),
                     z3::SatResult::Unknown => Err(Error::Unknown),
                 };
-                self.cache.insert(expr, res.clone());
+                self.stats.solver_time += start.elapsed();
+                self.cache.insert(expr.clone(), res.clone());
                 res
             }
         }
@@ -0,0 +1,384 @@
+use crate::ast;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::iter;
+
+/// Control-flow graph of a program. Instruction-level nodes are instruction
+/// indices, plus the exit node at `prog.0.len()`. Block-level nodes are indices
+/// into `blocks()`, and the last block is an empty exit block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cfg {
+    succs: Vec<Vec<usize>>,
+    preds: Vec<Vec<usize>>,
+    blocks: Vec<BasicBlock>,
+    insn_blocks: Vec<usize>,
+    block_succs: Vec<Vec<usize>>,
+    block_preds: Vec<Vec<usize>>,
+    idoms: Vec<Option<usize>>,
+    loops: Vec<NaturalLoop>,
+    loop_depths: Vec<usize>,
+}
+
+/// Straight-line run of instructions. Only the last instruction can be a
+/// branch, and only the first can be a branch target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BasicBlock {
+    /// Index of the first instruction
+    pub start: usize,
+
+    /// Index after the last instruction
+    pub end: usize,
+}
+
+/// Loop formed by a matching '[' and ']'
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NaturalLoop {
+    /// Index of the '['
+    pub open: usize,
+
+    /// Index of the ']'
+    pub close: usize,
+
+    /// Block the ']' jumps back to, which dominates the loop body
+    pub header: usize,
+
+    /// Blocks in the loop, including the header, in ascending order
+    pub blocks: Vec<usize>,
+}
+
+impl Cfg {
+    pub fn new(prog: &ast::Prog) -> Self {
+        let num_nodes = prog.0.len() + 1;
+        let mut succs = vec![vec![]; num_nodes];
+        let mut preds = vec![vec![]; num_nodes];
+        for (insn_ptr, insn) in prog.0.iter().enumerate() {
+            succs[insn_ptr] = match insn {
+                ast::Insn::JmpIfZero(target) | ast::Insn::JmpIfNonZero(target) => {
+                    vec![*target, insn_ptr + 1]
+                }
+                _ => vec![insn_ptr + 1],
+            };
+            for &succ in &succs[insn_ptr] {
+                preds[succ].push(insn_ptr);
+            }
+        }
+
+        let mut leaders = vec![false; num_nodes];
+        leaders[0] = true;
+        leaders[num_nodes - 1] = true;
+        for (insn_ptr, succs) in succs.iter().enumerate() {
+            if succs.len() > 1 {
+                for &succ in succs.iter().chain(iter::once(&(insn_ptr + 1))) {
+                    leaders[succ] = true;
+                }
+            }
+        }
+        let mut blocks = vec![];
+        let mut insn_blocks = vec![0; num_nodes];
+        for (insn_ptr, &leader) in leaders.iter().enumerate() {
+            if leader && insn_ptr > 0 {
+                blocks.push(BasicBlock {
+                    start: blocks.last().map_or(0, |block: &BasicBlock| block.end),
+                    end: insn_ptr,
+                });
+            }
+            insn_blocks[insn_ptr] = blocks.len();
+        }
+        blocks.push(BasicBlock {
+            start: num_nodes - 1,
+            end: num_nodes - 1,
+        });
+
+        let mut block_succs = vec![vec![]; blocks.len()];
+        let mut block_preds = vec![vec![]; blocks.len()];
+        for (block_idx, block) in blocks.iter().enumerate() {
+            if block.start == block.end {
+                continue;
+            }
+            for &succ in &succs[block.end - 1] {
+                block_succs[block_idx].push(insn_blocks[succ]);
+                block_preds[insn_blocks[succ]].push(block_idx);
+            }
+        }
+
+        let mut cfg = Self {
+            succs,
+            preds,
+            blocks,
+            insn_blocks,
+            block_succs,
+            block_preds,
+            idoms: vec![],
+            loops: vec![],
+            loop_depths: vec![],
+        };
+        cfg.idoms = cfg.compute_idoms();
+        cfg.loops = prog
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(close, insn)| match insn {
+                ast::Insn::JmpIfNonZero(target) => Some(cfg.natural_loop_of(target - 1, close)),
+                _ => None,
+            })
+            .collect();
+        cfg.loops.sort_by_key(|natural_loop| natural_loop.open);
+        cfg.loop_depths = vec![0; cfg.blocks.len()];
+        for natural_loop in &cfg.loops {
+            for &block in &natural_loop.blocks {
+                cfg.loop_depths[block] += 1;
+            }
+        }
+        cfg
+    }
+
+    /// Immediate dominator of each block, found by intersecting the dominator
+    /// sets of predecessors until they stop changing
+    fn compute_idoms(&self) -> Vec<Option<usize>> {
+        let num_blocks = self.blocks.len();
+        let mut doms = vec![vec![true; num_blocks]; num_blocks];
+        doms[0] = vec![false; num_blocks];
+        doms[0][0] = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 1..num_blocks {
+                let mut new_doms = vec![!self.block_preds[block].is_empty(); num_blocks];
+                for &pred in &self.block_preds[block] {
+                    for (dom, &pred_dom) in new_doms.iter_mut().zip(&doms[pred]) {
+                        *dom &= pred_dom;
+                    }
+                }
+                new_doms[block] = true;
+                if new_doms != doms[block] {
+                    doms[block] = new_doms;
+                    changed = true;
+                }
+            }
+        }
+        // The immediate dominator is the strict dominator dominated by all the
+        // others, which is the one with the most dominators
+        (0..num_blocks)
+            .map(|block| {
+                (0..num_blocks)
+                    .filter(|&dom| dom != block && doms[block][dom])
+                    .max_by_key(|&dom| doms[dom].iter().filter(|&&is_dom| is_dom).count())
+            })
+            .collect()
+    }
+
+    fn natural_loop_of(&self, open: usize, close: usize) -> NaturalLoop {
+        let header = self.insn_blocks[open + 1];
+        let mut in_loop = vec![false; self.blocks.len()];
+        in_loop[header] = true;
+        let mut stack = vec![self.insn_blocks[close]];
+        while let Some(block) = stack.pop() {
+            if !in_loop[block] {
+                in_loop[block] = true;
+                stack.extend(&self.block_preds[block]);
+            }
+        }
+        NaturalLoop {
+            open,
+            close,
+            header,
+            blocks: (0..self.blocks.len())
+                .filter(|&block| in_loop[block])
+                .collect(),
+        }
+    }
+
+    /// Index of the exit node
+    pub fn exit(&self) -> usize {
+        self.succs.len() - 1
+    }
+
+    /// Instructions that can execute right after `insn_ptr`. For a branch, the
+    /// jump target comes first.
+    pub fn insn_successors(&self, insn_ptr: usize) -> &[usize] {
+        &self.succs[insn_ptr]
+    }
+
+    /// Instructions that can execute right before `insn_ptr`
+    pub fn insn_predecessors(&self, insn_ptr: usize) -> &[usize] {
+        &self.preds[insn_ptr]
+    }
+
+    /// Basic blocks in program order
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Index of the block containing the instruction at `insn_ptr`
+    pub fn block_of(&self, insn_ptr: usize) -> usize {
+        self.insn_blocks[insn_ptr]
+    }
+
+    /// Blocks that can execute right after `block`. For a block ending in a
+    /// branch, the jump target comes first.
+    pub fn successors(&self, block: usize) -> &[usize] {
+        &self.block_succs[block]
+    }
+
+    /// Blocks that can execute right before `block`
+    pub fn predecessors(&self, block: usize) -> &[usize] {
+        &self.block_preds[block]
+    }
+
+    /// Closest block other than `block` that every path from the entry to
+    /// `block` goes through, or `None` for the entry block
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.idoms[block]
+    }
+
+    /// Whether every path from the entry to `block` goes through `dom`
+    pub fn dominates(&self, dom: usize, block: usize) -> bool {
+        let mut block = Some(block);
+        while let Some(b) = block {
+            if b == dom {
+                return true;
+            }
+            block = self.idoms[b];
+        }
+        false
+    }
+
+    /// Loops in order of their '['
+    pub fn loops(&self) -> &[NaturalLoop] {
+        &self.loops
+    }
+
+    /// Loop formed by the '[' or ']' at `insn_ptr`
+    pub fn natural_loop(&self, insn_ptr: usize) -> Option<&NaturalLoop> {
+        self.loops
+            .iter()
+            .find(|natural_loop| natural_loop.open == insn_ptr || natural_loop.close == insn_ptr)
+    }
+
+    /// Number of loops containing the instruction at `insn_ptr`. A '[' is
+    /// outside its own loop, since it runs once before the loop is entered,
+    /// and a ']' is inside it.
+    pub fn loop_depth(&self, insn_ptr: usize) -> usize {
+        self.loop_depths[self.insn_blocks[insn_ptr]]
+    }
+
+    /// Number of instructions executed on the shortest path from each node to
+    /// any of `targets`, not counting the target itself, or `None` if no
+    /// target is reachable
+    pub fn distances_to<I>(&self, targets: I) -> Vec<Option<usize>>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.weighted_distances_to(targets, 1)
+    }
+
+    /// Like `distances_to()`, but jumping back to the start of a loop costs
+    /// `back_edge_weight` instead of 1
+    pub fn weighted_distances_to<I>(
+        &self,
+        targets: I,
+        back_edge_weight: usize,
+    ) -> Vec<Option<usize>>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut distances = vec![None; self.succs.len()];
+        let mut heap = BinaryHeap::new();
+        for target in targets {
+            distances[target] = Some(0);
+            heap.push(Reverse((0, target)));
+        }
+        while let Some(Reverse((distance, node))) = heap.pop() {
+            if distances[node].is_some_and(|best| distance > best) {
+                continue;
+            }
+            for &pred in &self.preds[node] {
+                // The only edges going backwards are taken ']' jumps
+                let weight = if node <= pred { back_edge_weight } else { 1 };
+                let pred_distance = distance + weight;
+                if pred_distance < distances[pred].unwrap_or(usize::MAX) {
+                    distances[pred] = Some(pred_distance);
+                    heap.push(Reverse((pred_distance, pred)));
+                }
+            }
+        }
+        distances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distances() {
+        let prog = ast::Prog::from_str("+[->+<]>.").unwrap();
+        let cfg = Cfg::new(&prog);
+        assert_eq!(cfg.exit(), 9);
+        assert_eq!(cfg.insn_successors(1), &[7, 2]);
+        assert_eq!(cfg.insn_predecessors(2), &[1, 6]);
+        assert_eq!(
+            cfg.distances_to(vec![8]),
+            vec![
+                Some(3),
+                Some(2),
+                Some(6),
+                Some(5),
+                Some(4),
+                Some(3),
+                Some(2),
+                Some(1),
+                Some(0),
+                None
+            ]
+        );
+        assert_eq!(cfg.distances_to(vec![2])[5], Some(2));
+        assert_eq!(cfg.weighted_distances_to(vec![2], 10)[5], Some(11));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let prog = ast::Prog::from_str("+[->+<]>.").unwrap();
+        let cfg = Cfg::new(&prog);
+        assert_eq!(
+            cfg.blocks(),
+            &[
+                BasicBlock { start: 0, end: 2 },
+                BasicBlock { start: 2, end: 7 },
+                BasicBlock { start: 7, end: 9 },
+                BasicBlock { start: 9, end: 9 },
+            ]
+        );
+        assert_eq!(cfg.block_of(4), 1);
+        assert_eq!(cfg.successors(0), &[2, 1]);
+        assert_eq!(cfg.successors(1), &[1, 2]);
+        assert_eq!(cfg.predecessors(2), &[0, 1]);
+        assert_eq!(cfg.immediate_dominator(0), None);
+        assert_eq!(cfg.immediate_dominator(1), Some(0));
+        assert_eq!(cfg.immediate_dominator(2), Some(0));
+        assert_eq!(cfg.immediate_dominator(3), Some(2));
+        assert!(cfg.dominates(0, 3));
+        assert!(!cfg.dominates(1, 2));
+    }
+
+    #[test]
+    fn test_loops() {
+        let prog = ast::Prog::from_str(",[>[-]<-]").unwrap();
+        let cfg = Cfg::new(&prog);
+        let outer = cfg.natural_loop(8).unwrap();
+        assert_eq!((outer.open, outer.close), (1, 8));
+        assert_eq!(outer.header, cfg.block_of(2));
+        assert_eq!(outer.blocks, vec![1, 2, 3]);
+        let inner = cfg.natural_loop(3).unwrap();
+        assert_eq!(inner.header, cfg.block_of(4));
+        assert_eq!(inner.blocks, vec![2]);
+        assert_eq!(cfg.loops(), &[outer.clone(), inner.clone()]);
+        assert_eq!(
+            (0..9)
+                .map(|insn_ptr| cfg.loop_depth(insn_ptr))
+                .collect::<Vec<usize>>(),
+            vec![0, 0, 1, 1, 2, 2, 1, 1, 1]
+        );
+    }
+}
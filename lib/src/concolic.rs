@@ -0,0 +1,255 @@
+use crate::ast;
+use crate::cached_solver::CachedSolver;
+use crate::config::ExploreConfig;
+use crate::config::MergePolicy;
+use crate::coverage::Coverage;
+use crate::error::Error;
+use crate::error::Result;
+use crate::interp::Interpreter;
+use crate::state::State;
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Concolic explorer. Each run executes the program on a concrete input while
+/// building the symbolic path condition alongside it. New inputs come from
+/// negating the branch conditions of a run one at a time, like SAGE's
+/// generational search. Runs stop at `ExploreConfig::max_steps`, so set it
+/// for programs that may not terminate.
+pub struct Concolic {
+    prog: Rc<ast::Prog>,
+    config: Rc<ExploreConfig>,
+    pending: Vec<Pending>,
+    seen: HashSet<Vec<u8>>,
+    coverage: Coverage,
+}
+
+/// Input waiting to be run
+struct Pending {
+    input: Vec<u8>,
+
+    /// Index of the first branch of the run to negate. Branches before it
+    /// were already negated by an earlier generation.
+    bound: usize,
+
+    /// Number of branch directions first covered by the parent's run
+    score: usize,
+}
+
+/// Result of running the program on one input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConcolicRun {
+    /// Input bytes, including those read past the end of the given input,
+    /// which are the lowest value in `ExploreConfig::input_range`
+    pub input: Vec<u8>,
+
+    pub output: Vec<u8>,
+
+    /// Whether the program exited, as opposed to hitting the step budget or
+    /// an error
+    pub exited: bool,
+
+    /// Error that stopped the run
+    pub error: Option<Error>,
+
+    /// Number of branch directions this run covered first
+    pub new_branches: usize,
+}
+
+/// Symbolic branch taken during a run
+struct Branch<'ctx> {
+    /// State at the branch instruction
+    state: State<'ctx>,
+
+    /// Condition for the direction the run took
+    cond: z3::ast::Bool<'ctx>,
+}
+
+impl Concolic {
+    pub fn new(prog: Rc<ast::Prog>, config: ExploreConfig, seed: Vec<u8>) -> Result<Self> {
+        config.validate()?;
+        // Merged and veritested states don't follow a single concrete path
+        let config = config.merge(MergePolicy::Never).max_veritest_len(None);
+        let mut concolic = Self {
+            coverage: Coverage::new(Rc::clone(&prog)),
+            prog,
+            config: Rc::new(config),
+            pending: vec![],
+            seen: HashSet::new(),
+        };
+        concolic.add_seed(seed)?;
+        Ok(concolic)
+    }
+
+    /// Queue another input to run. Its bytes must be in
+    /// `ExploreConfig::input_range`.
+    pub fn add_seed(&mut self, input: Vec<u8>) -> Result<()> {
+        self.config.validate_input(&input)?;
+        if self.seen.insert(input.clone()) {
+            self.pending.push(Pending {
+                input,
+                bound: 0,
+                score: usize::MAX,
+            });
+        }
+        Ok(())
+    }
+
+    /// Instructions and branch directions covered by all runs so far
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    /// Run the pending input whose parent found the most new coverage, and
+    /// queue the inputs found by negating its branches. This returns
+    /// `Ok(None)` once there are no inputs left.
+    pub fn run_next<'ctx>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+    ) -> Result<Option<ConcolicRun>> {
+        let best = match (0..self.pending.len()).max_by_key(|&i| (self.pending[i].score, !i)) {
+            Some(best) => best,
+            None => return Ok(None),
+        };
+        let pending = self.pending.remove(best);
+        let (run, branches) = self.run(ctx, &pending.input)?;
+
+        for (idx, branch) in branches.iter().enumerate().skip(pending.bound) {
+            let negated = branch.cond.not();
+            let concrete = match branch.state.concretize_with(ctx, solver, &negated) {
+                Ok(concrete) => concrete,
+                Err(Error::Unsat) | Err(Error::Unknown) => continue,
+                Err(err) => return Err(err),
+            };
+            let mut input = concrete.input;
+            input.extend(run.input.iter().skip(input.len()));
+            if self.seen.insert(input.clone()) {
+                self.pending.push(Pending {
+                    input,
+                    bound: idx + 1,
+                    score: run.new_branches,
+                });
+            }
+        }
+        Ok(Some(run))
+    }
+
+    /// Run inputs until one makes the program exit with `output`
+    pub fn explore_until_output<'ctx>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        output: &[u8],
+    ) -> Result<Option<ConcolicRun>> {
+        while let Some(run) = self.run_next(ctx, solver)? {
+            if run.exited && run.output == output {
+                return Ok(Some(run));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Execute the program on `input` alongside an `Interpreter`, returning the
+    /// run and its symbolic branches
+    fn run<'ctx>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        input: &[u8],
+    ) -> Result<(ConcolicRun, Vec<Branch<'ctx>>)> {
+        let mut interp = Interpreter::new(
+            Rc::clone(&self.prog),
+            Rc::clone(&self.config),
+            input.to_vec(),
+        )?
+        .pad_input(*self.config.input_range.start());
+        let branches_before = self.coverage.branches_covered();
+        let mut branches = vec![];
+        let mut state = State::make_entry(ctx, Rc::clone(&self.prog), Rc::clone(&self.config));
+        let mut error = None;
+        self.coverage.record(&state);
+        while !state.exited() && state.steps < self.config.max_steps.unwrap_or(usize::MAX) {
+            let children = match state.step(ctx).and_then(|children| {
+                interp.step()?;
+                Ok(children)
+            }) {
+                Ok(children) => children,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            };
+            if children.len() > 1 {
+                if let Some(cond) = state.branch_condition(ctx, interp.insn_ptr) {
+                    branches.push(Branch {
+                        state: state.clone(),
+                        cond,
+                    });
+                }
+            }
+            state = match children
+                .into_iter()
+                .find(|child| child.insn_ptr == interp.insn_ptr)
+            {
+                Some(child) => child,
+                None => {
+                    return Err(Error::ReplayMismatch(format!(
+                        "replay went to instruction {} after {} steps, which cell \
+                         intervals ruled out",
+                        interp.insn_ptr, state.steps
+                    )))
+                }
+            };
+            self.coverage.record(&state);
+        }
+
+        let run = ConcolicRun {
+            input: interp.input().to_vec(),
+            output: interp.output,
+            exited: state.exited(),
+            error,
+            new_branches: self.coverage.branches_covered() - branches_before,
+        };
+        Ok((run, branches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generational() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        // Prints 3 if the input is at least 3
+        let prog = Rc::new(ast::Prog::from_str(",[-[-[[-]+++.[-]]]]").unwrap());
+        let config = ExploreConfig::default().mem_size(1);
+        let mut concolic = Concolic::new(prog, config, vec![0]).unwrap();
+
+        let run = concolic.run_next(&ctx, &mut solver).unwrap().unwrap();
+        assert_eq!(run.input, [0]);
+        assert!(run.exited);
+        assert!(run.output.is_empty());
+
+        let run = concolic
+            .explore_until_output(&ctx, &mut solver, &[3])
+            .unwrap()
+            .unwrap();
+        assert!(run.input[0] >= 3);
+    }
+
+    #[test]
+    fn test_seed_range() {
+        let prog = Rc::new(ast::Prog::from_str(",.").unwrap());
+        let config = ExploreConfig::default().input_range(b'a'..=b'z');
+        assert!(matches!(
+            Concolic::new(Rc::clone(&prog), config.clone(), b"A".to_vec()),
+            Err(Error::InvalidConfig(_))
+        ));
+        let mut concolic = Concolic::new(prog, config, b"a".to_vec()).unwrap();
+        assert!(concolic.add_seed(b"z{".to_vec()).is_err());
+    }
+}
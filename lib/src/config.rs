@@ -0,0 +1,249 @@
+use crate::error::Error;
+use crate::error::Result;
+
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use derive_setters::Setters;
+
+/// What happens when the data pointer moves off either end of the tape
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum TapePolicy {
+    /// Wrap around to the other end of the tape
+    Wrap,
+
+    /// Stop the state with `Error::TapeOverflow`
+    Error,
+}
+
+/// Value stored by ',' once all input has been consumed
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum EofBehavior {
+    /// Store 0 in the cell
+    Zero,
+
+    /// Store -1 (all bits set) in the cell
+    AllOnes,
+
+    /// Leave the cell unchanged
+    Unchanged,
+}
+
+/// Order in which `PathGroup` explores pending states
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum SearchStrategy {
+    /// Depth-first search. Explore the most recently created state first.
+    Dfs,
+
+    /// Breadth-first search. Explore the least recently created state first.
+    Bfs,
+
+    /// Explore the state closest in the control-flow graph to a branch
+    /// direction no feasible state has taken yet, like KLEE's nurs:covnew.
+    /// Ties go to the most recently created state.
+    Coverage,
+
+    /// Explore the state closest in the control-flow graph to the instruction
+    /// at this index, with loop back-edges costing
+    /// `ExploreConfig::back_edge_weight`. Ties go to the most recently created
+    /// state.
+    Directed(usize),
+}
+
+/// When `PathGroup` merges a new state into a pending state at the same join
+/// point. Merging states with `SearchStrategy::Dfs` happens when a loop exits
+/// where the jump past it is still pending.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum MergePolicy {
+    /// Never merge states
+    Never,
+
+    /// Merge whenever possible
+    Always,
+
+    /// Merge only if at most this many memory cells and output bytes differ,
+    /// since each one becomes an `ite` expression the solver has to reason
+    /// about
+    MaxDifferingCells(usize),
+}
+
+/// Global exploration budget that can run out
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Budget {
+    /// `ExploreConfig::max_states`
+    States,
+
+    /// `ExploreConfig::max_solver_calls`
+    SolverCalls,
+
+    /// `ExploreConfig::timeout`
+    Time,
+}
+
+/// Options for symbolic execution
+#[derive(Clone, Setters, PartialEq, Eq, Debug, Hash)]
+pub struct ExploreConfig {
+    /// Number of memory cells on the tape
+    pub mem_size: usize,
+
+    /// What happens when the data pointer moves off the tape
+    pub tape_policy: TapePolicy,
+
+    /// Width of each memory cell in bits. Input bytes are zero-extended or
+    /// truncated to this width, and output bytes are the low 8 bits of a cell.
+    pub cell_bits: u32,
+
+    /// Maximum number of input bytes. Reads after this many bytes have been
+    /// consumed behave according to `eof`. If this is `None`, every read
+    /// produces a fresh symbolic byte.
+    pub max_input_len: Option<usize>,
+
+    /// Value stored by ',' at end of input
+    pub eof: EofBehavior,
+
+    /// Values each symbolic input byte is constrained to
+    pub input_range: RangeInclusive<u8>,
+
+    /// Order in which pending states are explored
+    pub strategy: SearchStrategy,
+
+    /// Distance cost of jumping back to the start of a loop, used by
+    /// `SearchStrategy::Directed`. Higher values favor states that leave
+    /// loops over states that iterate them.
+    pub back_edge_weight: usize,
+
+    /// Maximum number of instructions a single state may execute. States
+    /// reaching this stop with `TerminationReason::StepBudget`.
+    pub max_steps: Option<usize>,
+
+    /// Track the range of values of each cell, and skip branch directions
+    /// the range rules out without asking the solver
+    pub intervals: bool,
+
+    /// When to merge states that reach the same join point
+    pub merge: MergePolicy,
+
+    /// Drop states identical to a state explored before, apart from
+    /// bookkeeping like the step count. This stops loops that return to an
+    /// earlier state without consuming input, but keeps every explored
+    /// state around to compare against.
+    pub dedup: bool,
+
+    /// Drop states whose path condition implies the path condition of a
+    /// state explored before that is otherwise identical. This costs a
+    /// solver query for each earlier such state.
+    pub subsumption: bool,
+
+    /// Maximum body length of a loop that runs at most once, like
+    /// `[>+<[-]]`, for both sides of its '[' to be executed as one state
+    /// instead of forking. `None` always forks.
+    pub max_veritest_len: Option<usize>,
+
+    /// Record the tree of forks, with the branch condition of each, for
+    /// `PathGroup::tree()`
+    pub record_tree: bool,
+
+    /// Replay each solution of `explore_until_output()` with the concrete
+    /// `Interpreter`, and return `Error::ReplayMismatch` if it doesn't write
+    /// the same output. Solutions from merged or veritested states aren't
+    /// replayed.
+    pub validate_solutions: bool,
+
+    /// Maximum number of states to explore before giving up
+    pub max_states: Option<usize>,
+
+    /// Maximum number of solver calls to make before giving up. Queries
+    /// answered from the solver cache don't count.
+    pub max_solver_calls: Option<usize>,
+
+    /// Maximum wall clock time for each call to `explore_until()`
+    pub timeout: Option<Duration>,
+
+    /// Timeout for each solver query, after which its result is
+    /// `Error::Unknown`
+    pub solver_timeout: Option<Duration>,
+}
+
+impl Default for ExploreConfig {
+    fn default() -> Self {
+        Self {
+            mem_size: 256,
+            tape_policy: TapePolicy::Wrap,
+            cell_bits: 8,
+            max_input_len: None,
+            eof: EofBehavior::Zero,
+            input_range: 0..=255,
+            strategy: SearchStrategy::Dfs,
+            back_edge_weight: 4,
+            max_steps: None,
+            intervals: false,
+            merge: MergePolicy::Never,
+            dedup: false,
+            subsumption: false,
+            max_veritest_len: None,
+            record_tree: false,
+            validate_solutions: false,
+            max_states: None,
+            max_solver_calls: None,
+            timeout: None,
+            solver_timeout: None,
+        }
+    }
+}
+
+impl ExploreConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.cell_bits == 0 || self.cell_bits > 64 {
+            return Err(Error::InvalidConfig(format!(
+                "cell width must be between 1 and 64 bits, got {}",
+                self.cell_bits
+            )));
+        }
+        if self.input_range.is_empty() {
+            return Err(Error::InvalidConfig(format!(
+                "input range {:?} is empty",
+                self.input_range
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that every byte of a concrete input is in `input_range`
+    pub fn validate_input(&self, input: &[u8]) -> Result<()> {
+        match input
+            .iter()
+            .position(|byte| !self.input_range.contains(byte))
+        {
+            Some(idx) => Err(Error::InvalidConfig(format!(
+                "input byte {} at index {} is outside the input range {:?}",
+                input[idx], idx, self.input_range
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Value of a cell with all bits set
+    pub fn cell_mask(&self) -> u64 {
+        match self.cell_bits {
+            64 => u64::MAX,
+            bits => (1 << bits) - 1,
+        }
+    }
+
+    /// Data pointer after '>' at `ptr`, or `None` if it moves off the tape
+    pub fn ptr_right(&self, ptr: usize) -> Option<usize> {
+        match self.tape_policy {
+            TapePolicy::Wrap => (ptr + 1).checked_rem(self.mem_size),
+            TapePolicy::Error => Some(ptr + 1).filter(|ptr| *ptr < self.mem_size),
+        }
+    }
+
+    /// Data pointer after '<' at `ptr`, or `None` if it moves off the tape
+    pub fn ptr_left(&self, ptr: usize) -> Option<usize> {
+        match (ptr.checked_sub(1), self.tape_policy) {
+            (Some(ptr), _) => Some(ptr),
+            (None, TapePolicy::Wrap) => self.mem_size.checked_sub(1),
+            (None, TapePolicy::Error) => None,
+        }
+    }
+}
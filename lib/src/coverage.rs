@@ -0,0 +1,235 @@
+use crate::ast;
+use crate::state::State;
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Instructions and branch directions reached by feasible states
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    prog: Rc<ast::Prog>,
+    insns: Vec<bool>,
+
+    /// Branch instruction indices along with whether the jump was taken
+    branches: BTreeSet<(usize, bool)>,
+}
+
+/// Summary of a `Coverage`
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CoverageReport {
+    pub insns_covered: usize,
+    pub insns_total: usize,
+    pub branches_covered: usize,
+
+    /// Two for each '[' and ']', one for the jump being taken and one for
+    /// falling through
+    pub branches_total: usize,
+
+    pub uncovered_insns: Vec<usize>,
+    pub uncovered_branches: Vec<(usize, bool)>,
+}
+
+/// Whether a jump from `insn_ptr` to `next_insn_ptr` was taken, or `None` if
+/// `insn_ptr` isn't a branch
+fn branch_taken(prog: &ast::Prog, insn_ptr: usize, next_insn_ptr: usize) -> Option<bool> {
+    match prog.0.get(insn_ptr)? {
+        ast::Insn::JmpIfZero(target) | ast::Insn::JmpIfNonZero(target) => {
+            Some(next_insn_ptr == *target)
+        }
+        _ => None,
+    }
+}
+
+fn is_branch(insn: &ast::Insn) -> bool {
+    matches!(insn, ast::Insn::JmpIfZero(_) | ast::Insn::JmpIfNonZero(_))
+}
+
+impl Coverage {
+    pub fn new(prog: Rc<ast::Prog>) -> Self {
+        Self {
+            insns: vec![false; prog.0.len()],
+            prog,
+            branches: BTreeSet::new(),
+        }
+    }
+
+    /// Mark the instruction `state` is at, and the branch direction that led
+    /// to it, as covered. `state` must be feasible.
+    pub(crate) fn record(&mut self, state: &State) {
+        self.record_insn(state.insn_ptr);
+        if let Some((insn_ptr, taken)) = self.branch_of(state) {
+            self.record_branch(insn_ptr, taken);
+        }
+    }
+
+    /// Branch direction `state` was reached by, if its last step branched
+    pub(crate) fn branch_of(&self, state: &State) -> Option<(usize, bool)> {
+        let prev_insn_ptr = state.prev_insn_ptr?;
+        branch_taken(&self.prog, prev_insn_ptr, state.insn_ptr).map(|taken| (prev_insn_ptr, taken))
+    }
+
+    pub(crate) fn record_insn(&mut self, insn_ptr: usize) {
+        if let Some(covered) = self.insns.get_mut(insn_ptr) {
+            *covered = true;
+        }
+    }
+
+    pub(crate) fn record_branch(&mut self, insn_ptr: usize, taken: bool) {
+        self.branches.insert((insn_ptr, taken));
+    }
+
+    pub fn is_insn_covered(&self, insn_ptr: usize) -> bool {
+        self.insns.get(insn_ptr).cloned().unwrap_or(false)
+    }
+
+    pub fn is_branch_covered(&self, insn_ptr: usize, taken: bool) -> bool {
+        self.branches.contains(&(insn_ptr, taken))
+    }
+
+    /// Branch directions covered, in order of instruction
+    pub fn covered_branches(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        self.branches.iter().cloned()
+    }
+
+    /// Number of branch directions covered
+    pub fn branches_covered(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Branch instructions with a direction that wasn't covered
+    pub fn uncovered_branch_insns(&self) -> impl Iterator<Item = usize> + '_ {
+        self.prog
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, insn)| is_branch(insn))
+            .map(|(insn_ptr, _)| insn_ptr)
+            .filter(move |&insn_ptr| {
+                !self.is_branch_covered(insn_ptr, true) || !self.is_branch_covered(insn_ptr, false)
+            })
+    }
+
+    /// Add the coverage of `other`, which must be of the same program,
+    /// returning whether it covered anything new
+    pub fn merge(&mut self, other: &Coverage) -> bool {
+        let mut new = false;
+        for (covered, other) in self.insns.iter_mut().zip(&other.insns) {
+            new |= *other && !*covered;
+            *covered |= other;
+        }
+        let branches = self.branches.len();
+        self.branches.extend(&other.branches);
+        new || self.branches.len() > branches
+    }
+
+    pub fn report(&self) -> CoverageReport {
+        let mut report = CoverageReport {
+            insns_total: self.insns.len(),
+            ..CoverageReport::default()
+        };
+        for (insn_ptr, insn) in self.prog.0.iter().enumerate() {
+            if self.is_insn_covered(insn_ptr) {
+                report.insns_covered += 1;
+            } else {
+                report.uncovered_insns.push(insn_ptr);
+            }
+            if is_branch(insn) {
+                for &taken in &[true, false] {
+                    report.branches_total += 1;
+                    if self.is_branch_covered(insn_ptr, taken) {
+                        report.branches_covered += 1;
+                    } else {
+                        report.uncovered_branches.push((insn_ptr, taken));
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Annotate the program's source text, which may contain comments. Each
+    /// line with instructions is followed by a line marking every instruction
+    /// with '+' if it and both its branch directions were covered, '~' if
+    /// only one branch direction was covered, and '!' if it wasn't reached.
+    pub fn annotate(&self, source: &str) -> String {
+        let report = self.report();
+        let mut out = String::new();
+        writeln!(
+            out,
+            "instructions: {}/{}, branches: {}/{}",
+            report.insns_covered,
+            report.insns_total,
+            report.branches_covered,
+            report.branches_total
+        )
+        .unwrap();
+        let mut insn_ptr = 0;
+        for (line_num, line) in source.lines().enumerate() {
+            let mut markers = String::new();
+            for c in line.chars() {
+                let is_insn = "<>+-.,[]".contains(c) && insn_ptr < self.insns.len();
+                if !is_insn {
+                    markers.push(if c == '\t' { '\t' } else { ' ' });
+                    continue;
+                }
+                let branches = [true, false]
+                    .iter()
+                    .filter(|&&taken| self.is_branch_covered(insn_ptr, taken))
+                    .count();
+                markers.push(if !self.is_insn_covered(insn_ptr) {
+                    '!'
+                } else if is_branch(&self.prog.0[insn_ptr]) && branches < 2 {
+                    '~'
+                } else {
+                    '+'
+                });
+                insn_ptr += 1;
+            }
+            writeln!(out, "{:5} | {}", line_num + 1, line).unwrap();
+            let markers = markers.trim_end();
+            if !markers.is_empty() {
+                writeln!(out, "      | {}", markers).unwrap();
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExploreConfig;
+
+    #[test]
+    fn test_annotate() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let source = "+ inc\n[ loop\n-]";
+        let prog = Rc::new(ast::Prog::from_str("+[-]").unwrap());
+        let config = Rc::new(ExploreConfig::default());
+
+        let mut coverage = Coverage::new(Rc::clone(&prog));
+        let mut state = State::make_entry(&ctx, prog, config);
+        coverage.record(&state);
+        for _ in 0..2 {
+            state = state.step(&ctx).unwrap().pop().unwrap();
+            coverage.record(&state);
+        }
+
+        let report = coverage.report();
+        assert_eq!(report.insns_covered, 3);
+        assert_eq!(report.uncovered_insns, vec![3]);
+        assert_eq!(
+            report.uncovered_branches,
+            vec![(1, true), (3, true), (3, false)]
+        );
+        assert_eq!(
+            coverage.annotate(source),
+            "instructions: 3/4, branches: 1/4\n    \
+             1 | + inc\n      | +\n    \
+             2 | [ loop\n      | ~\n    \
+             3 | -]\n      | +!\n"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::cached_solver::CachedSolver;
+use crate::error::Error;
+use crate::error::Result;
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use z3::ast::Ast;
+
+/// Values input bytes are compared against on explored paths, found by
+/// `PathGroup::extract_dictionary()`
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Dictionary {
+    /// Tokens in the order they were found, without duplicates
+    pub tokens: Vec<Vec<u8>>,
+
+    /// Whether every path was explored, as opposed to a budget running out
+    pub complete: bool,
+}
+
+impl Dictionary {
+    /// Dictionary in the format of AFL's `-x` option, one `kw<n>="..."` line
+    /// per token
+    pub fn to_afl(&self) -> String {
+        let mut afl = String::new();
+        for (idx, token) in self.tokens.iter().enumerate() {
+            write!(afl, "kw{}=\"", idx + 1).unwrap();
+            for &byte in token {
+                match byte {
+                    b'"' | b'\\' => write!(afl, "\\{}", byte as char),
+                    b' '..=b'~' => write!(afl, "{}", byte as char),
+                    _ => write!(afl, "\\x{:02x}", byte),
+                }
+                .unwrap();
+            }
+            afl.push_str("\"\n");
+        }
+        afl
+    }
+
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_afl())
+    }
+}
+
+/// Collects dictionary tokens from path conditions. Each equality between
+/// bitvectors depending on `input[i]` symbols is solved on its own, and the
+/// input bytes satisfying it become a token, one per run of consecutive
+/// indices. Branches only compare cells to zero, so this recovers constants
+/// built up by counter loops, where the literals in the condition would be
+/// their complement. Range comparisons from `ExploreConfig::input_range`
+/// aren't equalities, so they're skipped.
+pub(crate) struct TokenCollector<'ctx> {
+    /// Subexpressions already walked. Paths share the conditions of the
+    /// states they forked from.
+    seen: HashSet<z3::ast::Dynamic<'ctx>>,
+
+    known: HashSet<Vec<u8>>,
+    tokens: Vec<Vec<u8>>,
+
+    /// `ExploreConfig::solver_timeout`
+    timeout: Option<Duration>,
+}
+
+impl<'ctx> TokenCollector<'ctx> {
+    pub(crate) fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            seen: HashSet::new(),
+            known: HashSet::new(),
+            tokens: vec![],
+            timeout,
+        }
+    }
+
+    pub(crate) fn add_path(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        path: &z3::ast::Bool<'ctx>,
+    ) -> Result<()> {
+        let mut stack = vec![z3::ast::Dynamic::from_ast(path)];
+        while let Some(node) = stack.pop() {
+            if !node.is_app() || !self.seen.insert(node.clone()) {
+                continue;
+            }
+            let children = node.children();
+            if let [a, b] = children.as_slice() {
+                if matches!(
+                    node.decl().kind(),
+                    z3::DeclKind::EQ | z3::DeclKind::DISTINCT
+                ) {
+                    if let (Some(a), Some(b)) = (a.as_bv(), b.as_bv()) {
+                        self.add_comparison(ctx, solver, &node, a._eq(&b))?;
+                    }
+                }
+            }
+            stack.extend(children);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self, complete: bool) -> Dictionary {
+        Dictionary {
+            tokens: self.tokens,
+            complete,
+        }
+    }
+
+    fn add_comparison(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        node: &z3::ast::Dynamic<'ctx>,
+        eq: z3::ast::Bool<'ctx>,
+    ) -> Result<()> {
+        let mut inputs = input_syms(node);
+        if inputs.is_empty() {
+            return Ok(());
+        }
+        inputs.sort_by_key(|(idx, _)| *idx);
+        let model = match solver.solve_within(ctx, eq, self.timeout) {
+            Ok(model) => model,
+            Err(Error::Unsat) | Err(Error::Unknown) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let mut runs: Vec<(usize, Vec<u8>)> = vec![];
+        for (idx, sym) in inputs {
+            let byte = model
+                .eval(&sym)
+                .and_then(|val| val.as_u64())
+                .ok_or(Error::ModelCompletion)? as u8;
+            match runs.last_mut() {
+                Some((next, token)) if *next == idx => {
+                    token.push(byte);
+                    *next += 1;
+                }
+                _ => runs.push((idx + 1, vec![byte])),
+            }
+        }
+        for (_, token) in runs {
+            if self.known.insert(token.clone()) {
+                self.tokens.push(token);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `input[i]` symbols in `expr`, along with their index
+fn input_syms<'ctx>(expr: &z3::ast::Dynamic<'ctx>) -> Vec<(usize, z3::ast::BV<'ctx>)> {
+    let mut syms = vec![];
+    let mut seen = HashSet::new();
+    let mut stack = vec![expr.clone()];
+    while let Some(node) = stack.pop() {
+        if !node.is_app() || !seen.insert(node.clone()) {
+            continue;
+        }
+        if node.is_const() {
+            let idx = node
+                .decl()
+                .name()
+                .strip_prefix("input[")
+                .and_then(|name| name.strip_suffix(']'))
+                .and_then(|idx| idx.parse().ok());
+            if let (Some(idx), Some(sym)) = (idx, node.as_bv()) {
+                syms.push((idx, sym));
+            }
+        } else {
+            stack.extend(node.children());
+        }
+    }
+    syms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_afl() {
+        let dict = Dictionary {
+            tokens: vec![b"Z".to_vec(), vec![0, b'"', 0xff], b"a\\b".to_vec()],
+            complete: true,
+        };
+        assert_eq!(
+            dict.to_afl(),
+            "kw1=\"Z\"\nkw2=\"\\x00\\\"\\xff\"\nkw3=\"a\\\\b\"\n"
+        );
+    }
+}
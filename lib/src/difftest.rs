@@ -0,0 +1,324 @@
+use crate::arbitrary::ProgParams;
+use crate::arbitrary::Rng;
+use crate::ast;
+use crate::cached_solver::CachedSolver;
+use crate::config::ExploreConfig;
+use crate::config::TapePolicy;
+use crate::error::Error;
+use crate::error::Result;
+use crate::interp::InterpStop;
+use crate::interp::Interpreter;
+use crate::path_group::ExploreFnResult;
+use crate::path_group::PathGroup;
+use crate::state::ConcreteState;
+use crate::state::SymBytes;
+
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use derive_setters::Setters;
+
+/// Options for differential testing of the symbolic engine against the
+/// `brainfuck_rs` interpreter
+#[derive(Clone, Setters, PartialEq, Eq, Debug, Hash)]
+pub struct DiffTestOptions {
+    /// The `bf-reference` executable, which runs the program given as its
+    /// argument with `brainfuck_rs` on stdin and stdout. Integration tests
+    /// find it in `CARGO_BIN_EXE_bf-reference`.
+    pub reference: PathBuf,
+
+    /// Seed of the random programs and inputs
+    pub seed: u64,
+
+    /// Number of random cases to check
+    pub cases: usize,
+
+    /// Maximum number of instructions in a random program
+    pub max_prog_len: usize,
+
+    /// Maximum nesting depth of loops in a random program
+    pub max_loop_depth: usize,
+
+    /// Maximum number of bytes in a random input
+    pub max_input_len: usize,
+
+    /// Number of memory cells on the tape
+    pub mem_size: usize,
+
+    /// Cases that don't exit within this many steps are skipped
+    pub max_steps: usize,
+
+    /// Cases the reference doesn't finish within this time are skipped
+    pub reference_timeout: Duration,
+}
+
+impl DiffTestOptions {
+    pub fn new(reference: impl Into<PathBuf>) -> Self {
+        Self {
+            reference: reference.into(),
+            seed: 0,
+            cases: 100,
+            max_prog_len: 24,
+            max_loop_depth: 2,
+            max_input_len: 3,
+            mem_size: 8,
+            max_steps: 200,
+            reference_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Case where the symbolic engine disagrees with the reference interpreter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub source: String,
+    pub input: Vec<u8>,
+
+    /// Output of the reference interpreter
+    pub expected: Vec<u8>,
+
+    /// Concretized exit state on the path taking `input`, or `None` if no
+    /// such state was found
+    pub actual: Result<Option<ConcreteState>>,
+}
+
+/// Run random programs on random inputs through both the symbolic engine and
+/// the reference interpreter, returning the first disagreement shrunk to a
+/// minimal reproducer
+pub fn difftest(options: &DiffTestOptions) -> Result<Option<Mismatch>> {
+    let cfg = z3::Config::new();
+    let ctx = z3::Context::new(&cfg);
+    let mut solver = CachedSolver::new();
+    let mut rng = Rng::new(options.seed);
+    let params = ProgParams::default()
+        .max_len(options.max_prog_len)
+        .max_loop_depth(options.max_loop_depth);
+    for _ in 0..options.cases {
+        let source = random_prog(&mut rng, &params);
+        let input = random_input(&mut rng, options.max_input_len);
+        if check_case(&ctx, &mut solver, &source, &input, options)?.is_none() {
+            continue;
+        }
+        let (source, input) = shrink(&source, &input, |source, input| {
+            matches!(
+                check_case(&ctx, &mut solver, source, input, options),
+                Ok(Some(_))
+            )
+        });
+        return check_case(&ctx, &mut solver, &source, &input, options);
+    }
+    Ok(None)
+}
+
+/// Compare the output of the symbolic engine with the reference interpreter
+/// on one program and input. This returns `Ok(None)` if they agree, or if
+/// the case can't be compared: the reference has an unbounded tape of `i8`
+/// cells and panics at EOF, so cases leaving the tape, reading past the
+/// input, not exiting within `DiffTestOptions::max_steps` by the
+/// `Interpreter`, or making the reference fail are skipped.
+pub fn check_case<'ctx>(
+    ctx: &'ctx z3::Context,
+    solver: &mut CachedSolver<'ctx>,
+    source: &str,
+    input: &[u8],
+    options: &DiffTestOptions,
+) -> Result<Option<Mismatch>> {
+    let prog = Rc::new(ast::Prog::from_str(source)?);
+    let config = ExploreConfig::default()
+        .mem_size(options.mem_size)
+        .tape_policy(TapePolicy::Error)
+        .max_input_len(Some(input.len()))
+        .max_steps(Some(options.max_steps));
+    let mut interp = Interpreter::new(Rc::clone(&prog), Rc::new(config.clone()), input.to_vec())?;
+    if interp.run() != Ok(InterpStop::Exited) {
+        return Ok(None);
+    }
+    let expected = match run_reference(source, input, options)? {
+        Some(expected) => expected,
+        None => return Ok(None),
+    };
+
+    // Follow the path taking `input`, one step past the interpreter's count
+    // in case the engine counts steps differently
+    let config = config.max_steps(Some(interp.steps + 1));
+    let actual = PathGroup::make_entry(ctx, prog, config).and_then(|mut path_group| {
+        path_group.explore_until(ctx, solver, |state, solver| {
+            let input_eq = SymBytes::syms_eq(ctx, &state.input, input);
+            match state.concretize_with(ctx, solver, &input_eq) {
+                Ok(concrete) if state.exited() => ExploreFnResult::Done(concrete),
+                Ok(_) => ExploreFnResult::Valid,
+                Err(Error::Unsat) => ExploreFnResult::Invalid,
+                Err(err) => ExploreFnResult::Error(err),
+            }
+        })
+    });
+    if matches!(&actual, Ok(Some(state)) if state.output == expected) {
+        return Ok(None);
+    }
+    Ok(Some(Mismatch {
+        source: source.to_string(),
+        input: input.to_vec(),
+        expected,
+        actual,
+    }))
+}
+
+/// Output of the reference on `source` and `input`, or `None` if it failed
+/// or ran out of time. It prints each byte as the `char` of the same value,
+/// so the output is decoded from UTF-8.
+fn run_reference(source: &str, input: &[u8], options: &DiffTestOptions) -> Result<Option<Vec<u8>>> {
+    let io_error = |err: io::Error| {
+        Error::InvalidConfig(format!(
+            "can't run the reference {}: {}",
+            options.reference.display(),
+            err
+        ))
+    };
+    let mut child = Command::new(&options.reference)
+        .arg(source)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(io_error)?;
+    // The input fits in the pipe, and the reference may exit without reading
+    // it
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input);
+    }
+    let deadline = Instant::now() + options.reference_timeout;
+    let status = loop {
+        match child.try_wait().map_err(io_error)? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(None);
+            }
+            None => thread::sleep(Duration::from_millis(1)),
+        }
+    };
+    if !status.success() {
+        return Ok(None);
+    }
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout.read_to_string(&mut output).map_err(io_error)?;
+    }
+    Ok(Some(output.chars().map(|c| c as u32 as u8).collect()))
+}
+
+/// Greedily remove instructions, loops and input bytes, and lower input
+/// bytes, while `fails` keeps returning true. Programs stay balanced.
+pub fn shrink<F>(source: &str, input: &[u8], mut fails: F) -> (String, Vec<u8>)
+where
+    F: FnMut(&str, &[u8]) -> bool,
+{
+    let mut source = source.chars().collect::<Vec<char>>();
+    let mut input = input.to_vec();
+    'outer: loop {
+        for candidate in source_candidates(&source) {
+            let candidate_str = candidate.iter().collect::<String>();
+            if fails(&candidate_str, &input) {
+                source = candidate;
+                continue 'outer;
+            }
+        }
+        let source_str = source.iter().collect::<String>();
+        for candidate in input_candidates(&input) {
+            if fails(&source_str, &candidate) {
+                input = candidate;
+                continue 'outer;
+            }
+        }
+        return (source_str, input);
+    }
+}
+
+/// Sources with one instruction, one loop, or one pair of brackets removed
+fn source_candidates(source: &[char]) -> Vec<Vec<char>> {
+    let mut candidates = vec![];
+    let mut opens = vec![];
+    let without = |range: &[usize]| {
+        source
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !range.contains(idx))
+            .map(|(_, &c)| c)
+            .collect::<Vec<char>>()
+    };
+    for (idx, &c) in source.iter().enumerate() {
+        match c {
+            '[' => opens.push(idx),
+            ']' => {
+                if let Some(open) = opens.pop() {
+                    candidates.push(without(&(open..=idx).collect::<Vec<usize>>()));
+                    candidates.push(without(&[open, idx]));
+                }
+            }
+            _ => candidates.push(without(&[idx])),
+        }
+    }
+    candidates
+}
+
+/// Inputs with one byte removed, zeroed or halved
+fn input_candidates(input: &[u8]) -> Vec<Vec<u8>> {
+    let mut candidates = vec![];
+    for idx in 0..input.len() {
+        let mut removed = input.to_vec();
+        removed.remove(idx);
+        candidates.push(removed);
+        for val in [0, input[idx] / 2].iter() {
+            if *val != input[idx] {
+                let mut lowered = input.to_vec();
+                lowered[idx] = *val;
+                candidates.push(lowered);
+            }
+        }
+    }
+    candidates
+}
+
+fn random_prog(rng: &mut Rng, params: &ProgParams) -> String {
+    let choices = (0..params.max_len * 2)
+        .map(|_| rng.next() as u8)
+        .collect::<Vec<u8>>();
+    params.build(&choices)
+}
+
+/// Input of mostly small bytes, so loops counting them down terminate
+fn random_input(rng: &mut Rng, max_len: usize) -> Vec<u8> {
+    let len = rng.below(max_len + 1);
+    (0..len)
+        .map(|_| match rng.below(4) {
+            0 => rng.below(256) as u8,
+            _ => rng.below(8) as u8,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shrink() {
+        // Pretend any program outputting after a loop fails
+        let (source, input) = shrink("+,[->+<]>-.<", &[5, 7], |source, _| {
+            source
+                .find(']')
+                .is_some_and(|close| source[close..].contains('.'))
+        });
+        assert_eq!(source, "[].");
+        assert!(input.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use crate::config::Budget;
+
+use std::fmt;
+
+/// Errors returned by brainfsym
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// The brainf*** source failed to parse
+    Parse(String),
+
+    /// The path constraints can't be satisfied
+    Unsat,
+
+    /// The solver couldn't decide whether the path constraints can be satisfied
+    Unknown,
+
+    /// The solver produced a model, but it couldn't be used to evaluate a
+    /// symbolic value of the state
+    ModelCompletion,
+
+    /// The exploration options are invalid
+    InvalidConfig(String),
+
+    /// Exploration stopped because a budget ran out
+    BudgetExhausted(Budget),
+
+    /// Exploration was stopped with a `CancelToken`
+    Cancelled,
+
+    /// The data pointer moved outside the tape
+    TapeOverflow {
+        /// Index of the instruction that moved the data pointer
+        insn_ptr: usize,
+    },
+
+    /// Replaying a solution with the concrete interpreter reached a
+    /// different state than the solver, which is a bug in the engine. This
+    /// holds a diff of the two states.
+    ReplayMismatch(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(err) => write!(f, "parse error: {}", err),
+            Error::Unsat => write!(f, "path constraints are unsatisfiable"),
+            Error::Unknown => write!(f, "solver returned unknown"),
+            Error::ModelCompletion => write!(f, "failed evaluating symbolic value in model"),
+            Error::InvalidConfig(err) => write!(f, "invalid config: {}", err),
+            Error::BudgetExhausted(budget) => write!(f, "{:?} budget exhausted", budget),
+            Error::Cancelled => write!(f, "exploration cancelled"),
+            Error::TapeOverflow { insn_ptr } => {
+                write!(
+                    f,
+                    "data pointer moved off the tape at instruction {}",
+                    insn_ptr
+                )
+            }
+            Error::ReplayMismatch(diff) => {
+                write!(f, "engine bug: solution failed concrete replay\n{}", diff)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
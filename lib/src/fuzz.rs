@@ -0,0 +1,317 @@
+use crate::arbitrary::Rng;
+use crate::ast;
+use crate::cached_solver::CachedSolver;
+use crate::config::ExploreConfig;
+use crate::config::MergePolicy;
+use crate::config::SearchStrategy;
+use crate::coverage::Coverage;
+use crate::error::Error;
+use crate::error::Result;
+use crate::interp::Interpreter;
+use crate::path_group::ExploreFnResult;
+use crate::path_group::PathGroup;
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use derive_setters::Setters;
+
+/// Options for `HybridFuzzer`
+#[derive(Clone, Setters, PartialEq, Eq, Debug, Hash)]
+pub struct FuzzOptions {
+    /// Seed of the mutations
+    pub seed: u64,
+
+    /// Total number of inputs to execute
+    pub max_execs: usize,
+
+    /// Number of executions in a row without new coverage after which the
+    /// fuzzer has plateaued and asks the symbolic engine for new seeds
+    pub plateau: usize,
+
+    /// Maximum length of a mutated input
+    pub max_input_len: usize,
+
+    /// Maximum number of instructions to execute per input
+    pub max_steps: usize,
+
+    /// Maximum number of states the symbolic engine explores per plateau
+    pub max_states: usize,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            max_execs: 100_000,
+            plateau: 1000,
+            max_input_len: 64,
+            max_steps: 100_000,
+            max_states: 10_000,
+        }
+    }
+}
+
+/// Counters describing a fuzzing campaign
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct FuzzStats {
+    /// Inputs executed by the interpreter
+    pub execs: usize,
+
+    /// Times the fuzzer plateaued and the symbolic engine was run
+    pub plateaus: usize,
+
+    /// Inputs from the symbolic engine that covered something new
+    pub solved_seeds: usize,
+}
+
+/// Hybrid fuzzer in the style of Driller. Inputs are mutated and executed
+/// with the concrete `Interpreter`, keeping those that cover something new.
+/// When mutation stops finding new coverage, the symbolic engine explores
+/// with `SearchStrategy::Coverage` and solves for inputs taking the branch
+/// directions the fuzzer hasn't.
+pub struct HybridFuzzer {
+    prog: Rc<ast::Prog>,
+    config: Rc<ExploreConfig>,
+    options: FuzzOptions,
+    rng: Rng,
+    corpus: Vec<Vec<u8>>,
+    coverage: Coverage,
+    stats: FuzzStats,
+
+    /// Executions since the last one that covered something new
+    since_new: usize,
+
+    /// Whether the symbolic engine explored every path without finding new
+    /// coverage, so running it again is pointless
+    symbolic_done: bool,
+}
+
+impl HybridFuzzer {
+    /// Start from `seeds`, or the empty input if there are none. Seed bytes
+    /// must be in `ExploreConfig::input_range`.
+    pub fn new(
+        prog: Rc<ast::Prog>,
+        config: ExploreConfig,
+        options: FuzzOptions,
+        seeds: Vec<Vec<u8>>,
+    ) -> Result<Self> {
+        config.validate()?;
+        for seed in &seeds {
+            config.validate_input(seed)?;
+        }
+        // Merged and veritested states don't record the branch directions
+        // they took
+        let config = config.merge(MergePolicy::Never).max_veritest_len(None);
+        let mut fuzzer = Self {
+            coverage: Coverage::new(Rc::clone(&prog)),
+            prog,
+            config: Rc::new(config),
+            rng: Rng::new(options.seed),
+            options,
+            corpus: vec![],
+            stats: FuzzStats::default(),
+            since_new: 0,
+            symbolic_done: false,
+        };
+        if seeds.is_empty() {
+            fuzzer.execute(vec![])?;
+        }
+        for seed in seeds {
+            fuzzer.execute(seed)?;
+        }
+        Ok(fuzzer)
+    }
+
+    /// Inputs that covered a new instruction or branch direction, in the
+    /// order they were found
+    pub fn corpus(&self) -> &[Vec<u8>] {
+        &self.corpus
+    }
+
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    pub fn stats(&self) -> &FuzzStats {
+        &self.stats
+    }
+
+    /// Fuzz until `FuzzOptions::max_execs` inputs were executed or every
+    /// branch direction is covered
+    pub fn run<'ctx>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+    ) -> Result<()> {
+        while self.stats.execs < self.options.max_execs {
+            if self.coverage.uncovered_branch_insns().next().is_none() {
+                break;
+            }
+            if self.since_new >= self.options.plateau && !self.symbolic_done {
+                self.solve_uncovered(ctx, solver)?;
+                self.since_new = 0;
+            }
+            self.fuzz_one()?;
+        }
+        Ok(())
+    }
+
+    /// Mutate a random corpus input and execute it
+    pub fn fuzz_one(&mut self) -> Result<()> {
+        let idx = self.rng.below(self.corpus.len().max(1));
+        let mut input = self.corpus.get(idx).cloned().unwrap_or_default();
+        for _ in 0..=self.rng.below(4) {
+            self.mutate(&mut input);
+        }
+        input.truncate(self.options.max_input_len);
+        let (lo, hi) = (
+            *self.config.input_range.start(),
+            *self.config.input_range.end(),
+        );
+        input
+            .iter_mut()
+            .for_each(|byte| *byte = (*byte).clamp(lo, hi));
+        self.execute(input)?;
+        Ok(())
+    }
+
+    /// Explore symbolically, executing an input for each state taking a
+    /// branch direction not covered yet. This returns the number of inputs
+    /// that covered something new.
+    pub fn solve_uncovered<'ctx>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+    ) -> Result<usize> {
+        self.stats.plateaus += 1;
+        let config = (*self.config)
+            .clone()
+            .strategy(SearchStrategy::Coverage)
+            .max_states(Some(self.options.max_states));
+        let mut path_group = PathGroup::make_entry(ctx, Rc::clone(&self.prog), config)?;
+        let coverage = &self.coverage;
+        let mut targeted = HashSet::new();
+        let mut inputs = vec![];
+        let res = path_group.explore_until(ctx, solver, |state, solver| {
+            let branch = match coverage.branch_of(state) {
+                Some(branch) if !coverage.is_branch_covered(branch.0, branch.1) => branch,
+                _ => return ExploreFnResult::<()>::Valid,
+            };
+            if targeted.insert(branch) {
+                match state.concretize(ctx, solver) {
+                    Ok(concrete) => inputs.push(concrete.input),
+                    Err(err) => return ExploreFnResult::Error(err),
+                }
+            }
+            ExploreFnResult::Valid
+        });
+        let exhausted = match res {
+            Ok(_) => true,
+            Err(Error::BudgetExhausted(_)) => false,
+            Err(err) => return Err(err),
+        };
+
+        let mut found = 0;
+        for input in inputs {
+            if self.execute(input)? {
+                found += 1;
+            }
+        }
+        self.stats.solved_seeds += found;
+        self.symbolic_done = exhausted && found == 0;
+        Ok(found)
+    }
+
+    /// Run `input`, adding it to the corpus if it covers something new
+    fn execute(&mut self, input: Vec<u8>) -> Result<bool> {
+        self.stats.execs += 1;
+        let config = Rc::new(
+            (*self.config)
+                .clone()
+                .max_steps(Some(self.options.max_steps)),
+        );
+        let mut interp =
+            Interpreter::new(Rc::clone(&self.prog), config, input.clone())?.track_coverage();
+        // Coverage up to a tape overflow still counts
+        let _ = interp.run();
+        let new = interp
+            .coverage()
+            .is_some_and(|coverage| self.coverage.merge(coverage));
+        if !new {
+            self.since_new += 1;
+            return Ok(false);
+        }
+        self.since_new = 0;
+        self.corpus.push(input);
+        Ok(true)
+    }
+
+    /// Apply one random AFL-style mutation
+    fn mutate(&mut self, input: &mut Vec<u8>) {
+        const INTERESTING: [u8; 6] = [0, 1, 0x7f, 0x80, 0xfe, 0xff];
+        let idx = self.rng.below(input.len().max(1));
+        match (self.rng.below(7), input.is_empty()) {
+            (0, false) => input[idx] ^= 1 << self.rng.below(8),
+            (1, false) => input[idx] = self.rng.next() as u8,
+            (2, false) => input[idx] = INTERESTING[self.rng.below(INTERESTING.len())],
+            (3, false) => input[idx] = input[idx].wrapping_add(1 + self.rng.below(16) as u8),
+            (4, false) => input[idx] = input[idx].wrapping_sub(1 + self.rng.below(16) as u8),
+            (5, false) => {
+                input.remove(idx);
+            }
+            _ => {
+                // Insert a byte, or splice in the tail of a corpus input
+                let at = self.rng.below(input.len() + 1);
+                let other = self
+                    .corpus
+                    .get(self.rng.below(self.corpus.len().max(1)))
+                    .filter(|other| !other.is_empty());
+                match other {
+                    Some(other) if self.rng.below(2) == 0 => {
+                        let start = self.rng.below(other.len());
+                        input.splice(at..at, other[start..].iter().cloned());
+                    }
+                    _ => input.insert(at, self.rng.next() as u8),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hybrid() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        // Each outer loop is skipped only if its byte is 90 or 77, which
+        // mutation alone is unlikely to find
+        let prog = Rc::new(
+            ast::Prog::from_str(concat!(
+                ",>+++++++++[<---------->-]<[[-]]",
+                ",>+++++++[<----------->-]<[[-]]",
+            ))
+            .unwrap(),
+        );
+        let options = FuzzOptions::default().max_execs(500).plateau(100);
+        let mut fuzzer =
+            HybridFuzzer::new(prog, ExploreConfig::default(), options, vec![]).unwrap();
+        fuzzer.run(&ctx, &mut solver).unwrap();
+        assert!(fuzzer.stats().solved_seeds > 0);
+        assert!(fuzzer.coverage().is_branch_covered(27, true));
+        assert!(fuzzer.coverage().is_branch_covered(58, true));
+    }
+
+    #[test]
+    fn test_seed_range() {
+        let prog = Rc::new(ast::Prog::from_str(",.").unwrap());
+        let config = ExploreConfig::default().input_range(b'0'..=b'9');
+        let res = HybridFuzzer::new(prog, config, FuzzOptions::default(), vec![b"12a".to_vec()]);
+        assert!(matches!(res, Err(Error::InvalidConfig(_))));
+    }
+}
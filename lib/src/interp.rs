@@ -0,0 +1,491 @@
+use crate::ast;
+use crate::config::EofBehavior;
+use crate::config::ExploreConfig;
+use crate::config::TapePolicy;
+use crate::coverage::Coverage;
+use crate::error::Error;
+use crate::error::Result;
+use crate::state::ConcreteState;
+
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// Concrete interpreter with the same cell width, EOF, tape and step
+/// semantics as `State`. Runs of '+'/'-' and '>'/'<' and the loops `[-]` and
+/// `[+]` are executed as single operations, falling back to one instruction
+/// at a time when an operation would overflow the tape or the step budget.
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    prog: Rc<ast::Prog>,
+    config: Rc<ExploreConfig>,
+
+    /// Folded operation starting at each instruction, if any
+    ops: Vec<Option<Op>>,
+
+    pub mem: Vec<u64>,
+    pub insn_ptr: usize,
+    pub data_ptr: usize,
+    pub output: Vec<u8>,
+
+    /// Number of instructions executed
+    pub steps: usize,
+
+    /// Given input, followed by any pad bytes read past its end
+    input: Vec<u8>,
+    input_pos: usize,
+    pad: Option<u8>,
+    coverage: Option<Coverage>,
+}
+
+/// Why `Interpreter::run` stopped
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum InterpStop {
+    /// The program exited
+    Exited,
+
+    /// The interpreter executed `ExploreConfig::max_steps` instructions
+    StepBudget,
+}
+
+/// Several instructions executed at once
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Op {
+    /// A run of `len` '+' and '-' adding `delta` to the cell
+    Add { delta: u64, len: usize },
+
+    /// A run of `len` '>', or '<' if `right` is false
+    Move { right: bool, len: usize },
+
+    /// `[-]`, or `[+]` if `inc` is true
+    Clear { inc: bool },
+}
+
+impl Op {
+    /// Fold the instructions starting at each index
+    fn fold(prog: &ast::Prog, mask: u64) -> Vec<Option<Self>> {
+        let insns = &prog.0;
+        let mut ops = vec![None; insns.len()];
+        let mut insn_ptr = 0;
+        while insn_ptr < insns.len() {
+            let run_len = |pred: fn(&ast::Insn) -> bool| {
+                insns[insn_ptr..]
+                    .iter()
+                    .take_while(|insn| pred(insn))
+                    .count()
+            };
+            let (op, len) = match &insns[insn_ptr..] {
+                [ast::Insn::JmpIfZero(_), body @ (ast::Insn::Dec | ast::Insn::Inc), ast::Insn::JmpIfNonZero(_), ..] =>
+                {
+                    let inc = *body == ast::Insn::Inc;
+                    (Some(Op::Clear { inc }), 3)
+                }
+                [ast::Insn::Inc | ast::Insn::Dec, ..] => {
+                    let len = run_len(|insn| matches!(insn, ast::Insn::Inc | ast::Insn::Dec));
+                    let delta = insns[insn_ptr..insn_ptr + len]
+                        .iter()
+                        .fold(0u64, |delta, insn| match insn {
+                            ast::Insn::Inc => delta.wrapping_add(1),
+                            _ => delta.wrapping_sub(1),
+                        });
+                    let op = Op::Add {
+                        delta: delta & mask,
+                        len,
+                    };
+                    (Some(op).filter(|_| len > 1), len)
+                }
+                [insn @ (ast::Insn::Right | ast::Insn::Left), ..] => {
+                    let right = *insn == ast::Insn::Right;
+                    let len = if right {
+                        run_len(|insn| *insn == ast::Insn::Right)
+                    } else {
+                        run_len(|insn| *insn == ast::Insn::Left)
+                    };
+                    (Some(Op::Move { right, len }).filter(|_| len > 1), len)
+                }
+                _ => (None, 1),
+            };
+            ops[insn_ptr] = op;
+            insn_ptr += len;
+        }
+        ops
+    }
+}
+
+impl Interpreter {
+    /// Start at the beginning of `prog` with zeroed memory. Reads past the
+    /// end of `input` behave according to `ExploreConfig::eof`. This returns
+    /// `Error::InvalidConfig` if `config` is invalid.
+    pub fn new(prog: Rc<ast::Prog>, config: Rc<ExploreConfig>, input: Vec<u8>) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            ops: Op::fold(&prog, config.cell_mask()),
+            mem: vec![0; config.mem_size],
+            insn_ptr: 0,
+            data_ptr: 0,
+            output: vec![],
+            steps: 0,
+            input,
+            input_pos: 0,
+            pad: None,
+            coverage: None,
+            prog,
+            config,
+        })
+    }
+
+    /// Read `byte` past the end of the input instead of reaching EOF, until
+    /// `ExploreConfig::max_input_len` bytes have been read. This mirrors
+    /// `State` reading a fresh symbolic byte.
+    pub fn pad_input(mut self, byte: u8) -> Self {
+        self.pad = Some(byte);
+        self
+    }
+
+    /// Record the instructions and branch directions executed
+    pub fn track_coverage(mut self) -> Self {
+        let mut coverage = Coverage::new(Rc::clone(&self.prog));
+        coverage.record_insn(self.insn_ptr);
+        self.coverage = Some(coverage);
+        self
+    }
+
+    /// Coverage recorded so far, if `track_coverage` was called
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Input bytes read so far
+    pub fn input(&self) -> &[u8] {
+        &self.input[..self.input_pos]
+    }
+
+    pub fn exited(&self) -> bool {
+        self.insn_ptr == self.prog.0.len()
+    }
+
+    /// Current state, comparable with a solution from `State::concretize`
+    pub fn to_concrete(&self) -> ConcreteState {
+        ConcreteState {
+            mem: self.mem.clone(),
+            insn_ptr: self.insn_ptr,
+            data_ptr: self.data_ptr,
+            input: self.input().to_vec(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Replay the input of `solution` and check that the program writes the
+    /// same output, returning `Error::ReplayMismatch` if it doesn't. Only the
+    /// output is compared, since a solution's state may have executed further
+    /// after writing it, or stand for several paths.
+    pub fn validate(
+        prog: Rc<ast::Prog>,
+        config: Rc<ExploreConfig>,
+        solution: &ConcreteState,
+    ) -> Result<()> {
+        let max_steps = config.max_steps;
+        let mut interp = Self::new(prog, config, solution.input.clone())?;
+        let mut stopped = None;
+        while interp.output.len() < solution.output.len() {
+            if interp.exited() {
+                stopped = Some("program exited".to_string());
+            } else if max_steps.is_some_and(|max| interp.steps >= max) {
+                stopped = Some(format!("step budget ran out after {} steps", interp.steps));
+            } else if let Err(err) = interp.step() {
+                stopped = Some(err.to_string());
+            }
+            if stopped.is_some() {
+                break;
+            }
+        }
+
+        let mut diff = vec![];
+        if let Some(stopped) = stopped {
+            diff.push(format!("replay stopped before the output: {}", stopped));
+        }
+        if interp.output != solution.output {
+            diff.push(format!(
+                "output: solver {:?}, replay {:?}",
+                solution.output, interp.output
+            ));
+        }
+        if diff.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ReplayMismatch(diff.join("\n")))
+        }
+    }
+
+    /// Run until the program exits or reaches `ExploreConfig::max_steps`
+    pub fn run(&mut self) -> Result<InterpStop> {
+        loop {
+            if self.exited() {
+                return Ok(InterpStop::Exited);
+            }
+            if self.config.max_steps.is_some_and(|max| self.steps >= max) {
+                return Ok(InterpStop::StepBudget);
+            }
+            if !self.run_op()? {
+                self.step()?;
+            }
+        }
+    }
+
+    /// Execute a single instruction, doing nothing if the program exited
+    pub fn step(&mut self) -> Result<()> {
+        let insn_ptr = self.insn_ptr;
+        let mask = self.config.cell_mask();
+        let next = match self.prog.0.get(insn_ptr) {
+            Some(ast::Insn::Right) => {
+                self.data_ptr = self
+                    .config
+                    .ptr_right(self.data_ptr)
+                    .ok_or_else(|| self.tape_overflow())?;
+                insn_ptr + 1
+            }
+            Some(ast::Insn::Left) => {
+                self.data_ptr = self
+                    .config
+                    .ptr_left(self.data_ptr)
+                    .ok_or_else(|| self.tape_overflow())?;
+                insn_ptr + 1
+            }
+            Some(ast::Insn::Inc) => {
+                let cell = self.cell_mut()?;
+                *cell = cell.wrapping_add(1) & mask;
+                insn_ptr + 1
+            }
+            Some(ast::Insn::Dec) => {
+                let cell = self.cell_mut()?;
+                *cell = cell.wrapping_sub(1) & mask;
+                insn_ptr + 1
+            }
+            Some(ast::Insn::Out) => {
+                let byte = *self.cell_mut()? as u8;
+                self.output.push(byte);
+                insn_ptr + 1
+            }
+            Some(ast::Insn::In) => {
+                let val = match self.read_byte() {
+                    Some(byte) => byte as u64 & mask,
+                    None => match self.config.eof {
+                        EofBehavior::Zero => 0,
+                        EofBehavior::AllOnes => mask,
+                        EofBehavior::Unchanged => *self.cell_mut()?,
+                    },
+                };
+                *self.cell_mut()? = val;
+                insn_ptr + 1
+            }
+            Some(&ast::Insn::JmpIfZero(target)) => self.jump(target, true)?,
+            Some(&ast::Insn::JmpIfNonZero(target)) => self.jump(target, false)?,
+            None => return Ok(()),
+        };
+        self.insn_ptr = next;
+        self.steps += 1;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_insn(next);
+        }
+        Ok(())
+    }
+
+    /// Execute the folded operation at the instruction pointer, returning
+    /// whether there was one that fit within the tape and step budget
+    fn run_op(&mut self) -> Result<bool> {
+        let op = match self.ops.get(self.insn_ptr).cloned().flatten() {
+            Some(op) => op,
+            None => return Ok(false),
+        };
+        let start = self.insn_ptr;
+        let budget = self.config.max_steps.map(|max| max - self.steps);
+        let mask = self.config.cell_mask();
+        let steps = match op {
+            Op::Add { delta, len } => {
+                if budget.is_some_and(|budget| budget < len) {
+                    return Ok(false);
+                }
+                let cell = self.cell_mut()?;
+                *cell = cell.wrapping_add(delta) & mask;
+                len
+            }
+            Op::Move { right, len } => {
+                if budget.is_some_and(|budget| budget < len) {
+                    return Ok(false);
+                }
+                let size = self.mem.len();
+                self.data_ptr = match (right, self.config.tape_policy) {
+                    (_, TapePolicy::Wrap) if size == 0 => return Ok(false),
+                    (true, TapePolicy::Wrap) => (self.data_ptr + len % size) % size,
+                    (false, TapePolicy::Wrap) => (self.data_ptr + size - len % size) % size,
+                    (true, TapePolicy::Error) if self.data_ptr + len < size => self.data_ptr + len,
+                    (false, TapePolicy::Error) if len <= self.data_ptr => self.data_ptr - len,
+                    (_, TapePolicy::Error) => return Ok(false),
+                };
+                len
+            }
+            Op::Clear { inc } => {
+                let val = *self.cell_mut()?;
+                // '[' once, then '-' or '+' and ']' for each iteration
+                let iters = match (val, inc) {
+                    (0, _) => 0,
+                    (val, false) => val,
+                    (val, true) => mask - val + 1,
+                };
+                let steps = usize::try_from(iters)
+                    .ok()
+                    .and_then(|iters| iters.checked_mul(2))
+                    .and_then(|steps| steps.checked_add(1));
+                let steps = match steps {
+                    Some(steps) if steps <= budget.unwrap_or(usize::MAX) => steps,
+                    _ => return Ok(false),
+                };
+                *self.cell_mut()? = 0;
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_branch(start, iters == 0);
+                    if iters > 0 {
+                        coverage.record_insn(start + 1);
+                        coverage.record_insn(start + 2);
+                        coverage.record_branch(start + 2, false);
+                    }
+                    if iters > 1 {
+                        coverage.record_branch(start + 2, true);
+                    }
+                }
+                steps
+            }
+        };
+        let len = match op {
+            Op::Add { len, .. } | Op::Move { len, .. } => len,
+            Op::Clear { .. } => 3,
+        };
+        if let Some(coverage) = &mut self.coverage {
+            // The body of a clear loop was recorded above
+            if !matches!(op, Op::Clear { .. }) {
+                (start + 1..start + len).for_each(|insn_ptr| coverage.record_insn(insn_ptr));
+            }
+            coverage.record_insn(start + len);
+        }
+        self.insn_ptr = start + len;
+        self.steps += steps;
+        Ok(true)
+    }
+
+    /// Next instruction after the '[' or ']' at the instruction pointer
+    fn jump(&mut self, target: usize, if_zero: bool) -> Result<usize> {
+        let taken = (*self.cell_mut()? == 0) == if_zero;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_branch(self.insn_ptr, taken);
+        }
+        Ok(if taken { target } else { self.insn_ptr + 1 })
+    }
+
+    /// Next input byte, or `None` at EOF
+    fn read_byte(&mut self) -> Option<u8> {
+        if Some(self.input_pos) == self.config.max_input_len {
+            return None;
+        }
+        if self.input_pos == self.input.len() {
+            self.input.push(self.pad?);
+        }
+        self.input_pos += 1;
+        Some(self.input[self.input_pos - 1])
+    }
+
+    fn cell_mut(&mut self) -> Result<&mut u64> {
+        let err = self.tape_overflow();
+        self.mem.get_mut(self.data_ptr).ok_or(err)
+    }
+
+    fn tape_overflow(&self) -> Error {
+        Error::TapeOverflow {
+            insn_ptr: self.insn_ptr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_interp(source: &str, config: ExploreConfig, input: &[u8]) -> Interpreter {
+        let prog = Rc::new(ast::Prog::from_str(source).unwrap());
+        Interpreter::new(prog, Rc::new(config), input.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_run() {
+        let mut interp = new_interp(
+            "++++++++[>++++++++<-]>+.,.,.",
+            ExploreConfig::default().eof(EofBehavior::AllOnes),
+            &[7],
+        );
+        assert_eq!(interp.run(), Ok(InterpStop::Exited));
+        assert_eq!(interp.output, b"A\x07\xff");
+        assert_eq!(interp.input(), [7]);
+
+        let mut interp = new_interp(",.,.", ExploreConfig::default(), &[]).pad_input(5);
+        assert_eq!(interp.run(), Ok(InterpStop::Exited));
+        assert_eq!(interp.output, [5, 5]);
+        assert_eq!(interp.to_concrete().input, [5, 5]);
+    }
+
+    #[test]
+    fn test_folded_steps() {
+        // Folded operations count every instruction they stand for
+        let mut interp = new_interp("+++[-]--[+]>>", ExploreConfig::default(), &[]);
+        assert_eq!(interp.run(), Ok(InterpStop::Exited));
+        assert_eq!(interp.steps, 3 + 7 + 2 + 5 + 2);
+        assert_eq!(interp.data_ptr, 2);
+
+        let config = ExploreConfig::default().max_steps(Some(6));
+        let mut interp = new_interp("+++[-]", config, &[]);
+        assert_eq!(interp.run(), Ok(InterpStop::StepBudget));
+        assert_eq!(interp.steps, 6);
+        assert_eq!(interp.mem[0], 2);
+
+        let config = ExploreConfig::default()
+            .mem_size(2)
+            .tape_policy(TapePolicy::Error);
+        let mut interp = new_interp("+>>>", config, &[]);
+        assert_eq!(interp.run(), Err(Error::TapeOverflow { insn_ptr: 2 }));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let prog = Rc::new(ast::Prog::from_str("+").unwrap());
+        let config = Rc::new(ExploreConfig::default().cell_bits(65));
+        assert!(matches!(
+            Interpreter::new(prog, config, vec![]),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let prog = Rc::new(ast::Prog::from_str(",[->+<]>.").unwrap());
+        let config = Rc::new(ExploreConfig::default().mem_size(2));
+        let mut solution = ConcreteState {
+            mem: vec![0, 3],
+            insn_ptr: 9,
+            data_ptr: 1,
+            input: vec![3],
+            output: vec![3],
+        };
+        let validate = |solution: &ConcreteState| {
+            Interpreter::validate(Rc::clone(&prog), Rc::clone(&config), solution)
+        };
+        assert_eq!(validate(&solution), Ok(()));
+
+        // Memory may differ if the state executed further
+        solution.mem[1] = 4;
+        assert_eq!(validate(&solution), Ok(()));
+
+        solution.output = vec![4];
+        assert_eq!(
+            validate(&solution),
+            Err(Error::ReplayMismatch(
+                "output: solver [4], replay [3]".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+/// Range of unsigned values a memory cell can hold, used to decide branches
+/// without the solver. The range never wraps around, so `lo <= hi`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Interval {
+    pub lo: u64,
+    pub hi: u64,
+}
+
+impl Interval {
+    pub fn constant(val: u64) -> Self {
+        Self { lo: val, hi: val }
+    }
+
+    /// Every value of a cell whose maximum value is `mask`
+    pub fn full(mask: u64) -> Self {
+        Self { lo: 0, hi: mask }
+    }
+
+    pub fn contains_zero(&self) -> bool {
+        self.lo == 0
+    }
+
+    pub fn contains_non_zero(&self) -> bool {
+        self.hi != 0
+    }
+
+    /// Values after adding one modulo `mask + 1`
+    pub fn inc(&self, mask: u64) -> Self {
+        if self.hi < mask {
+            Self {
+                lo: self.lo + 1,
+                hi: self.hi + 1,
+            }
+        } else if self.lo == mask {
+            Self::constant(0)
+        } else {
+            Self::full(mask)
+        }
+    }
+
+    /// Values after subtracting one modulo `mask + 1`
+    pub fn dec(&self, mask: u64) -> Self {
+        if self.lo > 0 {
+            Self {
+                lo: self.lo - 1,
+                hi: self.hi - 1,
+            }
+        } else if self.hi == 0 {
+            Self::constant(mask)
+        } else {
+            Self::full(mask)
+        }
+    }
+
+    /// Values that are also non-zero. The result is meaningless if
+    /// `contains_non_zero()` is false.
+    pub fn non_zero(&self) -> Self {
+        Self {
+            lo: self.lo.max(1),
+            hi: self.hi,
+        }
+    }
+
+    /// Smallest interval containing the values of both
+    pub fn join(&self, other: &Self) -> Self {
+        Self {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let mask = 255;
+        assert_eq!(Interval::constant(255).inc(mask), Interval::constant(0));
+        assert_eq!(Interval::constant(0).dec(mask), Interval::constant(mask));
+        assert_eq!(Interval { lo: 1, hi: 255 }.inc(mask), Interval::full(mask));
+        assert_eq!(
+            Interval { lo: 1, hi: 255 }.dec(mask),
+            Interval { lo: 0, hi: 254 }
+        );
+        assert!(!Interval { lo: 1, hi: 255 }.contains_zero());
+        assert_eq!(Interval::full(mask).non_zero(), Interval { lo: 1, hi: 255 });
+        assert_eq!(
+            Interval::constant(3).join(&Interval::constant(7)),
+            Interval { lo: 3, hi: 7 }
+        );
+    }
+}
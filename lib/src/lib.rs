@@ -1,13 +1,51 @@
 #[macro_use]
 extern crate log;
 
+mod arbitrary;
 pub mod ast;
 mod cached_solver;
+mod cfg;
+mod concolic;
+mod config;
+mod coverage;
+mod dict;
+#[cfg(any(test, feature = "difftest"))]
+mod difftest;
+mod error;
+mod fuzz;
+mod interp;
+mod interval;
+mod minimize;
+mod observer;
 mod path_group;
+mod pointer;
+mod session;
 mod state;
+mod stats;
+mod testgen;
+mod tree;
 
+pub use arbitrary::*;
 pub use cached_solver::*;
+pub use cfg::*;
+pub use concolic::*;
+pub use config::*;
+pub use coverage::*;
+pub use dict::*;
+#[cfg(any(test, feature = "difftest"))]
+pub use difftest::*;
+pub use error::*;
+pub use fuzz::*;
+pub use interp::*;
+pub use interval::*;
+pub use minimize::*;
+pub use observer::*;
 pub use path_group::*;
+pub use pointer::*;
+pub use session::*;
 pub use state::*;
+pub use stats::*;
+pub use testgen::*;
+pub use tree::*;
 
 pub use z3;
@@ -0,0 +1,284 @@
+use crate::ast;
+use crate::cached_solver::CachedSolver;
+use crate::config::ExploreConfig;
+use crate::error::Error;
+use crate::error::Result;
+use crate::interp::Interpreter;
+use crate::path_group::PathGroup;
+use crate::path_group::TerminationReason;
+use crate::state::ConcreteState;
+
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Input found by `minimize_input()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinimizedInput {
+    /// State where the input has written the output
+    pub state: ConcreteState,
+
+    /// Whether the solver showed there's no shorter input, as opposed to a
+    /// budget running out, a path reaching the step limit, or the
+    /// interpreter rejecting the solver's input
+    pub complete: bool,
+}
+
+/// Shrink the input of `solution`, which writes `output`, to the shortest
+/// and then smallest input still writing it. The solver queries share the
+/// budgets of `config`. This returns `Error::ReplayMismatch` if `solution`
+/// doesn't write `output`.
+pub fn minimize_input<'ctx>(
+    ctx: &'ctx z3::Context,
+    solver: &mut CachedSolver<'ctx>,
+    prog: Rc<ast::Prog>,
+    config: &ExploreConfig,
+    output: &[u8],
+    solution: &ConcreteState,
+) -> Result<MinimizedInput> {
+    config.validate()?;
+    let unbounded = Minimizer {
+        prog: Rc::clone(&prog),
+        config: Rc::new(config.clone()),
+        output,
+        deadline: config.timeout.map(|timeout| Instant::now() + timeout),
+        states_explored: 0,
+        solver_calls: 0,
+        ruled_out: false,
+    };
+    let steps = unbounded
+        .replay_steps(&solution.input)
+        .ok_or_else(|| Error::ReplayMismatch("solution doesn't write the output".to_string()))?;
+    // Candidates must write the output within ten times the steps of
+    // `solution`, unless `ExploreConfig::max_steps` says otherwise
+    let max_steps = config.max_steps.unwrap_or_else(|| steps.saturating_mul(10));
+    let mut minimizer = Minimizer {
+        config: Rc::new(config.clone().max_steps(Some(max_steps))),
+        ..unbounded
+    };
+
+    let mut best = minimizer
+        .replay(&solution.input)
+        .unwrap_or_else(|| solution.clone());
+    loop {
+        best = minimizer.shrink_concrete(best);
+        let len = match best.input.len().checked_sub(1) {
+            Some(len) => len,
+            None => {
+                return Ok(MinimizedInput {
+                    state: best,
+                    complete: true,
+                })
+            }
+        };
+        match minimizer.solve_shorter(ctx, solver, len) {
+            Ok(Some(shorter)) => best = shorter,
+            Ok(None) => {
+                return Ok(MinimizedInput {
+                    state: best,
+                    complete: minimizer.ruled_out,
+                })
+            }
+            Err(Error::BudgetExhausted(_)) => {
+                return Ok(MinimizedInput {
+                    state: best,
+                    complete: false,
+                })
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+struct Minimizer<'a> {
+    prog: Rc<ast::Prog>,
+    config: Rc<ExploreConfig>,
+    output: &'a [u8],
+
+    /// When `ExploreConfig::timeout` runs out for all solver queries
+    deadline: Option<Instant>,
+
+    /// Budgets spent by the solver queries so far
+    states_explored: usize,
+    solver_calls: usize,
+
+    /// Whether the last solver query showed there's no shorter input
+    ruled_out: bool,
+}
+
+impl Minimizer<'_> {
+    /// State once `input` has written as many bytes as the goal output, if
+    /// they're the goal output
+    fn replay(&self, input: &[u8]) -> Option<ConcreteState> {
+        self.run(input).map(|interp| interp.to_concrete())
+    }
+
+    fn replay_steps(&self, input: &[u8]) -> Option<usize> {
+        self.run(input).map(|interp| interp.steps)
+    }
+
+    fn run(&self, input: &[u8]) -> Option<Interpreter> {
+        // The config was validated by `minimize_input()`
+        let mut interp = Interpreter::new(
+            Rc::clone(&self.prog),
+            Rc::clone(&self.config),
+            input.to_vec(),
+        )
+        .ok()?;
+        while interp.output.len() < self.output.len() {
+            let out_of_steps = self.config.max_steps.is_some_and(|max| interp.steps >= max);
+            if interp.exited() || out_of_steps {
+                return None;
+            }
+            interp.step().ok()?;
+        }
+        Some(interp).filter(|interp| interp.output == self.output)
+    }
+
+    /// Try the shortest prefix, then dropping and lowering single bytes,
+    /// until none of them work
+    fn shrink_concrete(&self, mut best: ConcreteState) -> ConcreteState {
+        if let Some(prefix) = (0..best.input.len()).find_map(|len| self.replay(&best.input[..len]))
+        {
+            best = prefix;
+        }
+        'outer: loop {
+            for idx in 0..best.input.len() {
+                let mut dropped = best.input.clone();
+                dropped.remove(idx);
+                let byte = best.input[idx];
+                let lowered = [0, byte / 2, byte.saturating_sub(1)]
+                    .iter()
+                    .filter(|&&val| val < byte)
+                    .map(|&val| {
+                        let mut lowered = best.input.clone();
+                        lowered[idx] = val;
+                        lowered
+                    })
+                    .collect::<Vec<Vec<u8>>>();
+                for candidate in Some(dropped).into_iter().chain(lowered) {
+                    if let Some(state) = self.replay(&candidate) {
+                        best = state;
+                        continue 'outer;
+                    }
+                }
+            }
+            return best;
+        }
+    }
+
+    /// Ask the solver for an input of at most `len` bytes writing the goal
+    /// output, with what's left of the budgets. This returns `Ok(None)` if
+    /// there is none.
+    fn solve_shorter<'ctx>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        len: usize,
+    ) -> Result<Option<ConcreteState>> {
+        let max_input_len = self.config.max_input_len.map_or(len, |max| max.min(len));
+        let mut config = (*self.config).clone().max_input_len(Some(max_input_len));
+        config.timeout = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        config.max_states = config
+            .max_states
+            .map(|max| max.saturating_sub(self.states_explored));
+        config.max_solver_calls = config
+            .max_solver_calls
+            .map(|max| max.saturating_sub(self.solver_calls));
+        let mut path_group = PathGroup::make_entry(ctx, Rc::clone(&self.prog), config)?;
+        let res = path_group.explore_until_output(ctx, solver, self.output);
+        self.states_explored += path_group.stats().states_explored;
+        self.solver_calls += path_group.stats().solver_calls();
+        let found = res?;
+        self.ruled_out = !path_group
+            .terminations()
+            .contains_key(&TerminationReason::StepBudget);
+        let found = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        // The solver's input is only kept if the interpreter agrees
+        let replayed = self.replay(&found.input);
+        self.ruled_out &= replayed.is_some();
+        Ok(replayed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+        let config = ExploreConfig::default().mem_size(2);
+        let solution = |input: &[u8], output: &[u8]| ConcreteState {
+            mem: vec![],
+            insn_ptr: 0,
+            data_ptr: 0,
+            input: input.to_vec(),
+            output: output.to_vec(),
+        };
+
+        // Trailing bytes after the echo loop's last read are dropped
+        let prog = Rc::new(ast::Prog::from_str(",[.,]").unwrap());
+        let res = minimize_input(
+            &ctx,
+            &mut solver,
+            prog,
+            &config,
+            b"ab",
+            &solution(b"ab\0xyz", b"ab"),
+        )
+        .unwrap();
+        assert!(res.complete);
+        assert_eq!(res.state.input, b"ab");
+        assert_eq!(res.state.output, b"ab");
+
+        // Only the solver finds that one byte can make the sum
+        let prog = Rc::new(ast::Prog::from_str(",>,[-<+>]<.").unwrap());
+        let res = minimize_input(
+            &ctx,
+            &mut solver,
+            prog,
+            &config,
+            &[7],
+            &solution(&[3, 4], &[7]),
+        )
+        .unwrap();
+        assert_eq!(res.state.input, [7]);
+        assert_eq!(res.state.data_ptr, 0);
+
+        // Without any states to explore, the solver can't rule out an empty
+        // input
+        let prog = Rc::new(ast::Prog::from_str(",>,[-<+>]<.").unwrap());
+        let res = minimize_input(
+            &ctx,
+            &mut solver,
+            prog,
+            &config.clone().max_states(Some(0)),
+            &[7],
+            &solution(&[3, 4], &[7]),
+        )
+        .unwrap();
+        assert!(!res.complete);
+        assert_eq!(res.state.input, [7]);
+
+        // Adding 7 to a second byte of 0 would take too many steps
+        let prog = Rc::new(ast::Prog::from_str(",>,<[->+<]>.").unwrap());
+        let res = minimize_input(
+            &ctx,
+            &mut solver,
+            prog,
+            &config.clone().max_steps(Some(20)),
+            &[7],
+            &solution(&[0, 7], &[7]),
+        )
+        .unwrap();
+        assert!(!res.complete);
+        assert_eq!(res.state.input, [0, 7]);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::cached_solver::SolverResultModel;
+use crate::path_group::TerminationReason;
+use crate::state::State;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Callbacks for path exploration events. Register an observer with
+/// `PathGroup::add_observer()`. Every method does nothing by default.
+pub trait ExplorationObserver<'ctx> {
+    /// A state had more than one continuation
+    fn on_fork(&mut self, _parent: &State<'ctx>, _children: &[State<'ctx>]) {}
+
+    /// A state stopped before the program exited, other than by merging
+    fn on_prune(&mut self, _state: &State<'ctx>, _reason: &TerminationReason) {}
+
+    /// `state` was merged into the pending state `into`, which continues
+    /// exploring for both
+    fn on_merge(&mut self, _state: &State<'ctx>, _into: &State<'ctx>) {}
+
+    /// The program exited in a state
+    fn on_exit(&mut self, _state: &State<'ctx>) {}
+
+    /// A query is about to be answered by the solver or its cache
+    fn on_solver_query(&mut self, _query: &z3::ast::Bool<'ctx>) {}
+
+    /// A query was answered by the solver or its cache
+    fn on_solver_result(
+        &mut self,
+        _query: &z3::ast::Bool<'ctx>,
+        _result: &SolverResultModel<'ctx>,
+    ) {
+    }
+
+    /// A state executed '.', producing `state` with `byte` appended to its
+    /// output
+    fn on_output(&mut self, _state: &State<'ctx>, _byte: &z3::ast::BV<'ctx>) {}
+}
+
+/// Shared handle to an observer. The caller keeps a clone of the handle to
+/// read the observer's results after exploring.
+pub type ObserverRef<'ctx> = Rc<RefCell<dyn ExplorationObserver<'ctx> + 'ctx>>;
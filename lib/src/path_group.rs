@@ -1,15 +1,74 @@
 use crate::ast;
 use crate::cached_solver::CachedSolver;
-use crate::cached_solver::Error;
+use crate::cfg::Cfg;
+use crate::config::Budget;
+use crate::config::ExploreConfig;
+use crate::config::MergePolicy;
+use crate::config::SearchStrategy;
+use crate::coverage::Coverage;
+use crate::dict::Dictionary;
+use crate::dict::TokenCollector;
+use crate::error::Error;
+use crate::error::Result;
+use crate::interp::InterpStop;
+use crate::interp::Interpreter;
+use crate::observer::ExplorationObserver;
+use crate::observer::ObserverRef;
 use crate::state::ConcreteState;
 use crate::state::State;
+use crate::state::StepKind;
 use crate::state::SymBytes;
+use crate::stats::ExploreStats;
+use crate::stats::Progress;
+use crate::testgen::TestCase;
+use crate::testgen::TestSuite;
+use crate::tree::ExploreTree;
 
+use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::iter;
+use std::mem;
 use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering as AtomicOrdering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 pub struct PathGroup<'ctx> {
-    next: Vec<State<'ctx>>,
+    next: VecDeque<State<'ctx>>,
+    terminations: HashMap<TerminationReason, usize>,
+    config: Rc<ExploreConfig>,
+    cancel: CancelToken,
+    stats: ExploreStats,
+    progress: Option<Progress<'ctx>>,
+    observers: Vec<ObserverRef<'ctx>>,
+    tree: Option<ExploreTree<'ctx>>,
+
+    /// Id of the next path segment to fork
+    next_id: usize,
+    coverage: Coverage,
+    cfg: Cfg,
+
+    /// Distances to uncovered branches used by `SearchStrategy::Coverage`,
+    /// along with the number of covered branch directions they were computed
+    /// for
+    uncovered_distances: Option<(usize, Vec<Option<usize>>)>,
+
+    /// Distances to the target used by `SearchStrategy::Directed`, along with
+    /// the target they were computed for
+    target_distances: Option<(usize, Vec<Option<usize>>)>,
+
+    /// Search strategy, which `reach()` overrides while it runs
+    strategy: SearchStrategy,
+
+    /// Path conditions and step counts of the states explored so far, keyed
+    /// by the state with its path condition removed. This is only filled in
+    /// with `ExploreConfig::dedup` or `ExploreConfig::subsumption`.
+    visited: HashMap<State<'ctx>, Vec<(z3::ast::Bool<'ctx>, usize)>>,
 }
 
 /// Type returned by `explore_until()` callback
@@ -25,43 +84,668 @@ pub enum ExploreFnResult<T> {
     /// This state is valid. It is not the target state, but continuations
     /// should be computed.
     Valid,
+
+    /// Stop exploring and return the error
+    Error(Error),
+}
+
+/// Reason a state stopped being explored
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum TerminationReason {
+    /// The program exited
+    Exited,
+
+    /// The state executed `ExploreConfig::max_steps` instructions
+    StepBudget,
+
+    /// The path constraints can't be satisfied
+    Unsat,
+
+    /// The `explore_until()` callback returned `ExploreFnResult::Invalid`
+    Invalid,
+
+    /// The state was merged into a pending state at the same instruction,
+    /// which continues exploring for both
+    Merged,
+
+    /// An identical state was explored before
+    Duplicate,
+
+    /// A state explored before is identical except for a path condition
+    /// implied by this state's path condition
+    Subsumed,
+
+    /// Executing the next instruction failed, for example because the data
+    /// pointer moved off the tape
+    Error(Error),
+}
+
+/// Handle for stopping exploration from the `explore_until()` callback or
+/// another thread. Exploration returns `Error::Cancelled` before popping the
+/// next state.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+
+    fn clear(&self) {
+        self.0.store(false, AtomicOrdering::Relaxed);
+    }
 }
 
 impl<'ctx> PathGroup<'ctx> {
-    pub fn make_entry(ctx: &'ctx z3::Context, prog: Rc<ast::Prog>, mem_size: usize) -> Self {
-        Self {
-            next: vec![State::make_entry(ctx, prog, mem_size)],
+    pub fn make_entry(
+        ctx: &'ctx z3::Context,
+        prog: Rc<ast::Prog>,
+        config: ExploreConfig,
+    ) -> Result<Self> {
+        config.validate()?;
+        let config = Rc::new(config);
+        let entry = State::make_entry(ctx, prog, Rc::clone(&config));
+        Ok(Self {
+            tree: Some(ExploreTree::new(&entry)).filter(|_| config.record_tree),
+            next_id: entry.id + 1,
+            coverage: Coverage::new(Rc::clone(&entry.prog)),
+            cfg: Cfg::new(&entry.prog),
+            uncovered_distances: None,
+            target_distances: None,
+            strategy: config.strategy,
+            visited: HashMap::new(),
+            next: VecDeque::from(vec![entry]),
+            terminations: HashMap::new(),
+            config,
+            cancel: CancelToken::default(),
+            stats: ExploreStats {
+                states_created: 1,
+                max_frontier: 1,
+                ..ExploreStats::default()
+            },
+            progress: None,
+            observers: vec![],
+        })
+    }
+
+    pub fn config(&self) -> &ExploreConfig {
+        &self.config
+    }
+
+    /// Number of states that stopped being explored for each reason
+    pub fn terminations(&self) -> &HashMap<TerminationReason, usize> {
+        &self.terminations
+    }
+
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Use `cancel` instead of this path group's own token, clearing it so
+    /// tokens handed out earlier keep working on a new path group
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        cancel.clear();
+        self.cancel = cancel;
+    }
+
+    pub fn stats(&self) -> &ExploreStats {
+        &self.stats
+    }
+
+    /// Tree of the forks made so far, if `ExploreConfig::record_tree` is set
+    pub fn tree(&self) -> Option<&ExploreTree<'ctx>> {
+        self.tree.as_ref()
+    }
+
+    /// Instructions and branch directions reached by the feasible states
+    /// explored so far
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    /// Call `callback` with the exploration stats at most once every
+    /// `interval` while exploring
+    pub fn set_progress<F>(&mut self, interval: Duration, callback: F)
+    where
+        F: FnMut(&ExploreStats) + 'ctx,
+    {
+        self.progress = Some(Progress {
+            interval,
+            last: Instant::now(),
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Notify `observer` of exploration events. Keep a clone of the handle to
+    /// read the observer's results after exploring.
+    pub fn add_observer<O>(&mut self, observer: Rc<RefCell<O>>)
+    where
+        O: ExplorationObserver<'ctx> + 'ctx,
+    {
+        self.observers.push(observer);
+    }
+
+    fn notify<F>(&self, mut fcn: F)
+    where
+        F: FnMut(&mut dyn ExplorationObserver<'ctx>),
+    {
+        for observer in &self.observers {
+            fcn(&mut *observer.borrow_mut());
+        }
+    }
+
+    fn pop_next(&mut self) -> Option<State<'ctx>> {
+        match self.strategy {
+            SearchStrategy::Dfs => self.next.pop_back(),
+            SearchStrategy::Bfs => self.next.pop_front(),
+            SearchStrategy::Coverage => {
+                self.update_uncovered_distances();
+                let distances = &self.uncovered_distances.as_ref()?.1;
+                let (index, _) = self
+                    .next
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .min_by_key(|(_, state)| distances[state.insn_ptr].unwrap_or(usize::MAX))?;
+                self.next.remove(index)
+            }
+            SearchStrategy::Directed(target) => {
+                self.update_target_distances(target);
+                let distances = &self.target_distances.as_ref()?.1;
+                let (index, _) = self
+                    .next
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .min_by_key(|(_, state)| distances[state.insn_ptr].unwrap_or(usize::MAX))?;
+                self.next.remove(index)
+            }
+        }
+    }
+
+    /// Recompute the distances to `target` if the target changed
+    fn update_target_distances(&mut self, target: usize) {
+        match &self.target_distances {
+            Some((cached_target, _)) if *cached_target == target => {}
+            _ => {
+                let distances = self.target_distances(target);
+                self.target_distances = Some((target, distances));
+            }
+        }
+    }
+
+    fn target_distances(&self, target: usize) -> Vec<Option<usize>> {
+        if target > self.cfg.exit() {
+            return vec![None; self.cfg.exit() + 1];
+        }
+        self.cfg
+            .weighted_distances_to(iter::once(target), self.config.back_edge_weight)
+    }
+
+    /// Recompute the distances to uncovered branches if the coverage changed
+    fn update_uncovered_distances(&mut self) {
+        let branches_covered = self.coverage.branches_covered();
+        match &self.uncovered_distances {
+            Some((covered, _)) if *covered == branches_covered => {}
+            _ => {
+                let distances = self
+                    .cfg
+                    .distances_to(self.coverage.uncovered_branch_insns());
+                self.uncovered_distances = Some((branches_covered, distances));
+            }
+        }
+    }
+
+    fn terminate(&mut self, state: State<'ctx>, reason: TerminationReason) {
+        debug!("state terminated: {:?}", reason);
+        if let Some(tree) = &mut self.tree {
+            tree.set_termination(state.id, reason.clone());
+        }
+        match reason {
+            TerminationReason::Exited => {
+                self.stats.exited += 1;
+                self.notify(|observer| observer.on_exit(&state));
+            }
+            // Counted in `ExploreStats::states_merged` when merging
+            TerminationReason::Merged => {}
+            _ => {
+                self.stats.pruned += 1;
+                self.notify(|observer| observer.on_prune(&state, &reason));
+            }
+        }
+        *self.terminations.entry(reason).or_default() += 1;
+    }
+
+    fn add_continuations(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        state: State<'ctx>,
+    ) -> Result<()> {
+        if state.exited() {
+            self.terminate(state, TerminationReason::Exited);
+            return Ok(());
         }
+        if self.config.max_steps.is_some_and(|max| state.steps >= max) {
+            self.terminate(state, TerminationReason::StepBudget);
+            return Ok(());
+        }
+        self.stats.insns_executed += 1;
+        match state.step_with_kind(ctx) {
+            Ok((mut states, kind)) => {
+                match kind {
+                    StepKind::Executed => {}
+                    StepKind::Decided => self.stats.queries_avoided += 1,
+                    StepKind::Veritested => {
+                        self.stats.regions_veritested += 1;
+                        self.record_veritested(ctx, solver, &state)?;
+                        states = states
+                            .into_iter()
+                            .map(|state| state.prev_insn_ptr(None))
+                            .collect();
+                    }
+                }
+                if states.len() > 1 {
+                    states = states
+                        .into_iter()
+                        .map(|child| {
+                            let id = self.next_id;
+                            self.next_id += 1;
+                            if let Some(tree) = &mut self.tree {
+                                let condition = state.branch_condition(ctx, child.insn_ptr);
+                                tree.add_fork(&state, id, condition);
+                            }
+                            child.id(id).parent_id(Some(state.id))
+                        })
+                        .collect();
+                    self.stats.forks += 1;
+                    self.notify(|observer| observer.on_fork(&state, &states));
+                }
+                if let Some(ast::Insn::Out) = state.prog.0.get(state.insn_ptr) {
+                    for state in &states {
+                        if let Some(byte) = state.output.0.last() {
+                            self.notify(|observer| observer.on_output(state, byte));
+                        }
+                    }
+                }
+                self.stats.states_created += states.len();
+                for state in states {
+                    self.push_or_merge(ctx, solver, state)?;
+                }
+                self.stats.max_frontier = self.stats.max_frontier.max(self.next.len());
+            }
+            Err(err) => self.terminate(state, TerminationReason::Error(err)),
+        }
+        Ok(())
+    }
+
+    /// Merge `state` into a pending state if the merge policy allows it, and
+    /// add it to the pending states otherwise. Merging only happens at
+    /// instructions with more than one predecessor, where paths rejoin. The
+    /// branch directions both states arrived by are recorded first, since the
+    /// merged state can only have one.
+    fn push_or_merge(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        state: State<'ctx>,
+    ) -> Result<()> {
+        let is_join_point = self.cfg.insn_predecessors(state.insn_ptr).len() > 1;
+        let merge = self.config.merge;
+        let pending = self
+            .next
+            .iter()
+            .filter(|_| is_join_point)
+            .position(|pending| {
+                pending.can_merge(&state)
+                    && match merge {
+                        MergePolicy::Never => false,
+                        MergePolicy::Always => true,
+                        MergePolicy::MaxDifferingCells(max) => {
+                            pending.differing_cells(&state) <= max
+                        }
+                    }
+            });
+        let index = match pending {
+            Some(index) => index,
+            None => {
+                self.next.push_back(state);
+                return Ok(());
+            }
+        };
+        let pending = self.next[index].clone();
+        self.record_if_feasible(ctx, solver, &pending)?;
+        self.record_if_feasible(ctx, solver, &state)?;
+        self.next[index] = pending.merge(&state).prev_insn_ptr(None);
+        self.stats.states_merged += 1;
+        self.notify(|observer| observer.on_merge(&state, &pending));
+        self.terminate(state, TerminationReason::Merged);
+        Ok(())
+    }
+
+    /// Record the coverage of each side of the loop at `state`'s '[' that
+    /// can be taken, since it was veritested instead of forking
+    fn record_veritested(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        state: &State<'ctx>,
+    ) -> Result<()> {
+        let end = match state.prog.0.get(state.insn_ptr) {
+            Some(ast::Insn::JmpIfZero(end)) => *end,
+            _ => return Ok(()),
+        };
+        for next in [end, state.insn_ptr + 1] {
+            let condition = match state.branch_condition(ctx, next) {
+                Some(condition) => condition,
+                None => continue,
+            };
+            match state.concretize_with(ctx, solver, &condition) {
+                Ok(_) | Err(Error::Unknown) => {}
+                Err(Error::Unsat) => continue,
+                Err(err) => return Err(err),
+            }
+            self.coverage.record_branch(state.insn_ptr, next == end);
+            if next != end {
+                // The body, whose clear loops are covered as if they ran,
+                // and the ']' falling through
+                for insn_ptr in next..end {
+                    self.coverage.record_insn(insn_ptr);
+                }
+                self.coverage.record_branch(end - 1, false);
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the coverage of a state that hasn't been explored, unless its
+    /// path condition can't be satisfied
+    fn record_if_feasible(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        state: &State<'ctx>,
+    ) -> Result<()> {
+        if state.prev_insn_ptr.is_none() {
+            return Ok(());
+        }
+        match state.concretize(ctx, solver) {
+            Ok(_) | Err(Error::Unknown) => self.coverage.record(state),
+            Err(Error::Unsat) => {}
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// Record `state`, which must be feasible, as visited, returning why it
+    /// should be dropped if `ExploreConfig::dedup` or
+    /// `ExploreConfig::subsumption` finds an earlier equivalent state that
+    /// took no more steps
+    fn check_visited(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        state: &State<'ctx>,
+    ) -> Option<TerminationReason> {
+        if !self.config.dedup && !self.config.subsumption {
+            return None;
+        }
+        let timeout = self.config.solver_timeout;
+        let key = state.clone().path(z3::ast::Bool::from_bool(ctx, true));
+        let paths = self.visited.entry(key).or_default();
+        if self.config.dedup {
+            if let Some((_, steps)) = paths.iter_mut().find(|(path, _)| *path == state.path) {
+                if *steps <= state.steps {
+                    return Some(TerminationReason::Duplicate);
+                }
+                // Fewer steps leave more of `ExploreConfig::max_steps`, so
+                // explore this one too
+                *steps = state.steps;
+                return None;
+            }
+        }
+        if self.config.subsumption {
+            let subsumed = paths.iter().any(|(path, steps)| {
+                let not_implied = state.path.and(&[&path.not()]);
+                *steps <= state.steps
+                    && matches!(
+                        solver.solve_within(ctx, not_implied, timeout),
+                        Err(Error::Unsat)
+                    )
+            });
+            if subsumed {
+                return Some(TerminationReason::Subsumed);
+            }
+        }
+        paths.push((state.path.clone(), state.steps));
+        None
     }
 
-    fn add_continuations(&mut self, ctx: &'ctx z3::Context, state: &State<'ctx>) {
-        for state in state.step(ctx) {
-            self.next.push(state);
+    fn check_budgets(&self, deadline: Option<Instant>) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        if self
+            .config
+            .max_states
+            .is_some_and(|max| self.stats.states_explored >= max)
+        {
+            return Err(Error::BudgetExhausted(Budget::States));
+        }
+        if self
+            .config
+            .max_solver_calls
+            .is_some_and(|max| self.stats.solver_calls() >= max)
+        {
+            return Err(Error::BudgetExhausted(Budget::SolverCalls));
         }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(Error::BudgetExhausted(Budget::Time));
+        }
+        Ok(())
     }
 
+    /// Explore states until the callback returns `ExploreFnResult::Done`. This
+    /// returns `Ok(None)` if every path was explored, and an error if a budget
+    /// ran out or exploration was cancelled. Exploration can be resumed after
+    /// a budget error. States the solver returns unknown for are explored as
+    /// if they were feasible.
     pub fn explore_until<F, T>(
         &mut self,
         ctx: &'ctx z3::Context,
         solver: &mut CachedSolver<'ctx>,
         mut fcn: F,
-    ) -> Option<T>
+    ) -> Result<Option<T>>
+    where
+        F: FnMut(&State<'ctx>, &mut CachedSolver<'ctx>) -> ExploreFnResult<T>,
+    {
+        let observers = solver.swap_observers(self.observers.clone());
+        let res = self.explore_loop(ctx, solver, &mut fcn);
+        solver.swap_observers(observers);
+        res
+    }
+
+    fn explore_loop<F, T>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        fcn: &mut F,
+    ) -> Result<Option<T>>
     where
         F: FnMut(&State<'ctx>, &mut CachedSolver<'ctx>) -> ExploreFnResult<T>,
     {
+        let deadline = self.config.timeout.map(|timeout| Instant::now() + timeout);
         loop {
             debug!("num next: {}", self.next.len(),);
-            let state = self.next.pop()?;
-            trace!("state: {:#?}", state);
-            if let Err(Error::Unsat) = state.concretize(ctx, solver) {
-                continue;
+            if let Some(progress) = &mut self.progress {
+                progress.poll(&self.stats);
+            }
+            self.check_budgets(deadline)?;
+            let state = match self.pop_next() {
+                Some(state) => state,
+                None => return Ok(None),
+            };
+            self.stats.states_explored += 1;
+            let solver_stats = solver.stats();
+            let res = self.explore_state(ctx, solver, state, fcn);
+            self.stats.add_solver_stats(&solver_stats, &solver.stats());
+            if let Some(v) = res? {
+                return Ok(Some(v));
+            }
+        }
+    }
+
+    fn explore_state<F, T>(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        state: State<'ctx>,
+        fcn: &mut F,
+    ) -> Result<Option<T>>
+    where
+        F: FnMut(&State<'ctx>, &mut CachedSolver<'ctx>) -> ExploreFnResult<T>,
+    {
+        trace!("state: {:#?}", state);
+        // Only feasible states are recorded as visited. The solver can't
+        // rule out a path it returns unknown for, so that one is explored.
+        let reason = match state.concretize(ctx, solver) {
+            Ok(_) | Err(Error::Unknown) => self.check_visited(ctx, solver, &state),
+            Err(Error::Unsat) => Some(TerminationReason::Unsat),
+            Err(err) => return Err(err),
+        };
+        if let Some(reason) = reason {
+            self.terminate(state, reason);
+            return Ok(None);
+        }
+        self.coverage.record(&state);
+        match fcn(&state, solver) {
+            ExploreFnResult::Done(v) => return Ok(Some(v)),
+            ExploreFnResult::Invalid => self.terminate(state, TerminationReason::Invalid),
+            ExploreFnResult::Valid => self.add_continuations(ctx, solver, state)?,
+            ExploreFnResult::Error(err) => return Err(err),
+        }
+        Ok(None)
+    }
+
+    /// Find a state at the instruction at `insn_ptr`, using directed search
+    /// regardless of the configured strategy. States that can't reach the
+    /// instruction in the control-flow graph are terminated as
+    /// `TerminationReason::Invalid`. This returns `Ok(None)` if no feasible
+    /// path reaches the instruction.
+    pub fn reach(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+        insn_ptr: usize,
+    ) -> Result<Option<ConcreteState>> {
+        let distances = self.target_distances(insn_ptr);
+        let strategy = mem::replace(&mut self.strategy, SearchStrategy::Directed(insn_ptr));
+        let res = self.explore_until(ctx, solver, |state, solver| {
+            if state.insn_ptr == insn_ptr {
+                match state.concretize(ctx, solver) {
+                    Ok(state) => ExploreFnResult::Done(state),
+                    Err(err) => ExploreFnResult::Error(err),
+                }
+            } else if distances[state.insn_ptr].is_none() {
+                ExploreFnResult::Invalid
+            } else {
+                ExploreFnResult::Valid
+            }
+        });
+        self.strategy = strategy;
+        res
+    }
+
+    /// Explore every path, making a test case for each one that exits. If a
+    /// budget runs out, this returns the cases found so far with
+    /// `TestSuite::complete` set to false. Each input is replayed with the
+    /// concrete interpreter, returning `Error::ReplayMismatch` if it doesn't
+    /// exit with the same output.
+    pub fn generate_tests(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+    ) -> Result<TestSuite> {
+        let mut cases = vec![];
+        let mut inputs = HashSet::new();
+        let res = self.explore_until(ctx, solver, |state, solver| {
+            if !state.exited() {
+                return ExploreFnResult::<()>::Valid;
+            }
+            let concrete = match state.concretize(ctx, solver) {
+                Ok(concrete) => concrete,
+                Err(err) => return ExploreFnResult::Error(err),
+            };
+            if inputs.insert(concrete.input.clone()) {
+                cases.push((state.id, Rc::clone(&state.prog), concrete));
             }
-            match fcn(&state, solver) {
-                ExploreFnResult::Done(v) => return Some(v),
-                ExploreFnResult::Invalid => continue,
-                ExploreFnResult::Valid => self.add_continuations(ctx, &state),
+            ExploreFnResult::Valid
+        });
+        let complete = match res {
+            Ok(_) => true,
+            Err(Error::BudgetExhausted(_)) | Err(Error::Cancelled) => false,
+            Err(err) => return Err(err),
+        };
+
+        // Replay each input to find the branch directions it takes
+        let config = Rc::new(self.config().clone());
+        let mut suite = TestSuite {
+            cases: vec![],
+            complete,
+        };
+        for (path_id, prog, concrete) in cases {
+            let mut interp = Interpreter::new(prog, Rc::clone(&config), concrete.input.clone())?
+                .track_coverage();
+            let stop = interp.run();
+            if stop != Ok(InterpStop::Exited) {
+                return Err(Error::ReplayMismatch(format!(
+                    "path {}: solver exited, replay stopped with {:?}",
+                    path_id, stop
+                )));
+            }
+            if interp.output != concrete.output {
+                return Err(Error::ReplayMismatch(format!(
+                    "path {}: output: solver {:?}, replay {:?}",
+                    path_id, concrete.output, interp.output
+                )));
             }
+            suite.cases.push(TestCase {
+                path_id,
+                branches: interp
+                    .coverage()
+                    .map(|coverage| coverage.covered_branches().collect())
+                    .unwrap_or_default(),
+                input: concrete.input,
+                output: concrete.output,
+            });
         }
+        Ok(suite)
+    }
+
+    /// Collect the values input bytes are compared against on the path of
+    /// every explored state, as a fuzzer dictionary
+    pub fn extract_dictionary(
+        &mut self,
+        ctx: &'ctx z3::Context,
+        solver: &mut CachedSolver<'ctx>,
+    ) -> Result<Dictionary> {
+        let mut collector = TokenCollector::new(self.config.solver_timeout);
+        let res = self.explore_until(ctx, solver, |state, solver| {
+            match collector.add_path(ctx, solver, &state.path) {
+                Ok(()) => ExploreFnResult::<()>::Valid,
+                Err(err) => ExploreFnResult::Error(err),
+            }
+        });
+        let complete = match res {
+            Ok(_) => true,
+            Err(Error::BudgetExhausted(_)) | Err(Error::Cancelled) => false,
+            Err(err) => return Err(err),
+        };
+        Ok(collector.finish(complete))
     }
 
     pub fn explore_until_output(
@@ -69,7 +753,7 @@ impl<'ctx> PathGroup<'ctx> {
         ctx: &'ctx z3::Context,
         solver: &mut CachedSolver<'ctx>,
         output: &[u8],
-    ) -> Option<ConcreteState> {
+    ) -> Result<Option<ConcreteState>> {
         self.explore_until(ctx, solver, |state, solver| {
             let sym_len = state.output.0.len();
             let concr_len = output.len();
@@ -81,11 +765,28 @@ impl<'ctx> PathGroup<'ctx> {
                 Ordering::Greater => ExploreFnResult::Invalid,
                 Ordering::Less | Ordering::Equal => {
                     let output_eq = SymBytes::syms_eq(ctx, &state.output, output);
-                    let state = state.concretize_with(ctx, solver, &output_eq);
-                    match state {
-                        Ok(state) if cmp == Ordering::Equal => ExploreFnResult::Done(state),
+                    let concrete = state.concretize_with(ctx, solver, &output_eq);
+                    match concrete {
+                        Ok(concrete) if cmp == Ordering::Equal => {
+                            // A merged or veritested state's concrete path
+                            // is one of several it stands for
+                            let replayed = if state.config.validate_solutions && !state.merged {
+                                Interpreter::validate(
+                                    Rc::clone(&state.prog),
+                                    Rc::clone(&state.config),
+                                    &concrete,
+                                )
+                            } else {
+                                Ok(())
+                            };
+                            match replayed {
+                                Ok(()) => ExploreFnResult::Done(concrete),
+                                Err(err) => ExploreFnResult::Error(err),
+                            }
+                        }
                         Ok(_) | Err(Error::Unknown) => ExploreFnResult::Valid,
                         Err(Error::Unsat) => ExploreFnResult::Invalid,
+                        Err(err) => ExploreFnResult::Error(err),
                     }
                 }
             }
@@ -96,6 +797,9 @@ impl<'ctx> PathGroup<'ctx> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::TapePolicy;
+
+    use std::cell::Cell;
 
     #[test]
     fn test_add() {
@@ -104,13 +808,345 @@ mod tests {
         let mut solver = CachedSolver::new();
 
         let prog = ast::Prog::from_str(",>,[-<+>]<.").unwrap();
-        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), 16);
+        let config = ExploreConfig::default().mem_size(16);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
         let res = path_group
             .explore_until_output(&ctx, &mut solver, &[2])
+            .unwrap()
             .unwrap();
         assert_eq!(res.input.iter().sum::<u8>(), 2);
     }
 
+    #[test]
+    fn test_stats() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[-]").unwrap();
+        let config = ExploreConfig::default().mem_size(1);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let progress_calls = Rc::new(Cell::new(0));
+        let calls = Rc::clone(&progress_calls);
+        path_group.set_progress(Duration::from_secs(0), move |_| calls.set(calls.get() + 1));
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+
+        let stats = path_group.stats();
+        assert!(progress_calls.get() > 0);
+        assert_eq!(stats.exited, 1);
+        assert_eq!(stats.pruned, 2);
+        assert_eq!(stats.forks, 2);
+        assert_eq!(stats.states_created, 7);
+        assert_eq!(stats.states_explored, 7);
+        assert_eq!(stats.solver_queries, stats.states_explored);
+        assert_eq!(stats.insns_executed, 4);
+    }
+
+    #[test]
+    fn test_intervals() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[-],[-]").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(1)
+            .input_range(1..=255)
+            .intervals(true);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+
+        // Intervals decide both branches of the first loop, entering the
+        // second, and leaving it after the 255th iteration. Every other exit
+        // check of the second loop forks.
+        let stats = path_group.stats();
+        assert_eq!(stats.queries_avoided, 4);
+        assert_eq!(stats.forks, 254);
+        assert_eq!(stats.exited, 255);
+        assert_eq!(stats.pruned, 0);
+    }
+
+    #[derive(Default)]
+    struct CountingObserver {
+        forks: usize,
+        prunes: usize,
+        exits: usize,
+        queries: usize,
+        results: usize,
+        outputs: usize,
+    }
+
+    impl<'ctx> ExplorationObserver<'ctx> for CountingObserver {
+        fn on_fork(&mut self, _parent: &State<'ctx>, _children: &[State<'ctx>]) {
+            self.forks += 1;
+        }
+
+        fn on_prune(&mut self, _state: &State<'ctx>, _reason: &TerminationReason) {
+            self.prunes += 1;
+        }
+
+        fn on_exit(&mut self, _state: &State<'ctx>) {
+            self.exits += 1;
+        }
+
+        fn on_solver_query(&mut self, _query: &z3::ast::Bool<'ctx>) {
+            self.queries += 1;
+        }
+
+        fn on_solver_result(
+            &mut self,
+            _query: &z3::ast::Bool<'ctx>,
+            _result: &crate::SolverResultModel<'ctx>,
+        ) {
+            self.results += 1;
+        }
+
+        fn on_output(&mut self, _state: &State<'ctx>, _byte: &z3::ast::BV<'ctx>) {
+            self.outputs += 1;
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[-].").unwrap();
+        let config = ExploreConfig::default().mem_size(1);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let observer = Rc::new(RefCell::new(CountingObserver::default()));
+        path_group.add_observer(Rc::clone(&observer));
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+
+        let observer = observer.borrow();
+        let stats = path_group.stats();
+        assert_eq!(observer.forks, stats.forks);
+        assert_eq!(observer.prunes, stats.pruned);
+        assert_eq!(observer.exits, stats.exited);
+        assert_eq!(observer.queries, stats.solver_queries);
+        assert_eq!(observer.results, stats.solver_queries);
+        assert_eq!(observer.outputs, 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        // The path skipping the loop is still pending at the loop exit when
+        // the path through the loop gets there
+        let prog = ast::Prog::from_str(",[-]+.").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(1)
+            .input_range(0..=1)
+            .intervals(true)
+            .merge(MergePolicy::Always);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let mut outputs = vec![];
+        let res = path_group.explore_until(&ctx, &mut solver, |state, solver| {
+            if state.exited() {
+                outputs.push(state.concretize(&ctx, solver).unwrap().output);
+            }
+            ExploreFnResult::<()>::Valid
+        });
+        assert_eq!(res, Ok(None));
+
+        let stats = path_group.stats();
+        assert_eq!(stats.states_merged, 1);
+        assert_eq!(stats.exited, 1);
+        assert_eq!(stats.pruned, 0);
+        assert_eq!(outputs, [[1]]);
+
+        // Both ways of leaving the loop are covered
+        let coverage = path_group.coverage();
+        assert!(coverage.is_branch_covered(1, true));
+        assert!(coverage.is_branch_covered(3, false));
+    }
+
+    #[test]
+    fn test_veritest() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = Rc::new(ast::Prog::from_str(",[>+<[-]]>.").unwrap());
+        let config = ExploreConfig::default()
+            .mem_size(2)
+            .max_veritest_len(Some(8));
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::clone(&prog), config.clone()).unwrap();
+        let mut exit_steps = vec![];
+        let res = path_group.explore_until(&ctx, &mut solver, |state, _| {
+            if state.exited() {
+                exit_steps.push(state.steps);
+            }
+            ExploreFnResult::<()>::Valid
+        });
+        assert_eq!(res, Ok(None));
+        let stats = path_group.stats();
+        assert_eq!(stats.regions_veritested, 1);
+        assert_eq!(stats.forks, 0);
+        assert_eq!(stats.exited, 1);
+
+        // Steps of the longest path, where the clear loop runs 255 times
+        assert_eq!(exit_steps, [519]);
+        let coverage = path_group.coverage();
+        assert!(coverage.is_insn_covered(6));
+        assert!(coverage.is_branch_covered(1, true));
+        assert!(coverage.is_branch_covered(1, false));
+        assert!(coverage.is_branch_covered(8, false));
+
+        let mut path_group = PathGroup::make_entry(&ctx, prog, config).unwrap();
+        let res = path_group.explore_until_output(&ctx, &mut solver, &[1]);
+        assert_ne!(res.unwrap().unwrap().input, [0]);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[]").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(1)
+            .intervals(true)
+            .dedup(true);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+        assert_eq!(path_group.terminations().len(), 1);
+        assert_eq!(path_group.terminations()[&TerminationReason::Duplicate], 1);
+
+        // Without intervals, the loop's exits are infeasible rather than
+        // duplicates of each other
+        let prog = ast::Prog::from_str("+[]").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(1)
+            .max_steps(Some(20))
+            .dedup(true);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+        assert_eq!(path_group.terminations()[&TerminationReason::Duplicate], 1);
+        assert!(path_group.terminations()[&TerminationReason::Unsat] >= 2);
+
+        // The loop returns to the same state, but with a stronger path
+        // condition after checking the first cell
+        let prog = ast::Prog::from_str(",>,[<]").unwrap();
+        let config = ExploreConfig::default().mem_size(2).subsumption(true);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+        assert!(path_group
+            .terminations()
+            .contains_key(&TerminationReason::Subsumed));
+    }
+
+    #[test]
+    fn test_tree() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str(",[-]").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(1)
+            .max_steps(Some(8))
+            .record_tree(true);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+
+        let tree = path_group.tree().unwrap();
+        assert_eq!(tree.nodes().len(), path_group.stats().forks * 2 + 1);
+        for (id, node) in tree.nodes().iter().enumerate() {
+            assert_eq!(node.id, id);
+            assert_eq!(node.parent_id.is_some(), node.condition.is_some());
+        }
+        for (reason, count) in path_group.terminations() {
+            let nodes = tree
+                .nodes()
+                .iter()
+                .filter(|node| node.termination.as_ref() == Some(reason))
+                .count();
+            assert_eq!(nodes, *count);
+        }
+        let fork_counts = tree.fork_counts();
+        assert_eq!(
+            fork_counts.keys().cloned().collect::<Vec<usize>>(),
+            vec![1, 3]
+        );
+        assert!(tree.to_dot().starts_with("digraph exploration {"));
+        assert!(tree.to_json().contains("\"fork_counts\""));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        // The second loop is never entered, since the first loop exits with
+        // the cell at zero
+        let prog = ast::Prog::from_str(",[-][+]").unwrap();
+        let config = ExploreConfig::default().mem_size(1).input_range(0..=3);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+
+        let report = path_group.coverage().report();
+        assert_eq!(report.insns_total, 7);
+        assert_eq!(report.uncovered_insns, vec![5, 6]);
+        assert_eq!(report.branches_total, 6);
+        assert_eq!(
+            report.uncovered_branches,
+            vec![(4, false), (6, true), (6, false)]
+        );
+    }
+
+    #[test]
+    fn test_coverage_search() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str(",[>,],[-]").unwrap();
+        let config = ExploreConfig::default()
+            .strategy(SearchStrategy::Coverage)
+            .max_states(Some(200));
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Err(Error::BudgetExhausted(Budget::States)));
+
+        let report = path_group.coverage().report();
+        assert_eq!(report.insns_covered, report.insns_total);
+        assert_eq!(report.branches_covered, report.branches_total);
+    }
+
+    #[test]
+    fn test_reach() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = Rc::new(ast::Prog::from_str(",[-[-[.]]]").unwrap());
+        let config = ExploreConfig::default().mem_size(1);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::clone(&prog), config).unwrap();
+        let res = path_group.reach(&ctx, &mut solver, 6).unwrap().unwrap();
+        assert_eq!(res.insn_ptr, 6);
+        assert!(res.input[0] >= 3);
+
+        let prog = Rc::new(ast::Prog::from_str("[.]").unwrap());
+        let config = ExploreConfig::default().mem_size(1);
+        let mut path_group = PathGroup::make_entry(&ctx, prog, config).unwrap();
+        assert_eq!(path_group.reach(&ctx, &mut solver, 1), Ok(None));
+    }
+
     #[test]
     fn test_rev() {
         let cfg = z3::Config::new();
@@ -118,10 +1154,132 @@ mod tests {
         let mut solver = CachedSolver::new();
 
         let prog = ast::Prog::from_str("+[>,]+[<.-]").unwrap();
-        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), 16);
+        let config = ExploreConfig::default().mem_size(16);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
         let res = path_group
             .explore_until_output(&ctx, &mut solver, b"ABC")
+            .unwrap()
             .unwrap();
         assert_eq!(res.input, b"CBA\x00");
     }
+
+    #[test]
+    fn test_rev_eof() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[>,]+[<.-]").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(16)
+            .max_input_len(Some(3))
+            .input_range(1..=255);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group
+            .explore_until_output(&ctx, &mut solver, b"ABC")
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.input, b"CBA");
+    }
+
+    #[test]
+    fn test_state_budget() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[]").unwrap();
+        let config = ExploreConfig::default().mem_size(1).max_states(Some(100));
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until_output(&ctx, &mut solver, b"A");
+        assert_eq!(res, Err(Error::BudgetExhausted(Budget::States)));
+    }
+
+    #[test]
+    fn test_termination_reasons() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str(",[<]+[-]").unwrap();
+        let config = ExploreConfig::default()
+            .mem_size(1)
+            .tape_policy(TapePolicy::Error);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let res = path_group.explore_until(&ctx, &mut solver, |_, _| ExploreFnResult::<()>::Valid);
+        assert_eq!(res, Ok(None));
+
+        let reasons = path_group.terminations();
+        assert!(reasons.contains_key(&TerminationReason::Exited));
+        assert!(reasons.contains_key(&TerminationReason::Unsat));
+        assert!(
+            reasons.contains_key(&TerminationReason::Error(Error::TapeOverflow {
+                insn_ptr: 2
+            }))
+        );
+    }
+
+    #[test]
+    fn test_cancel() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        let prog = ast::Prog::from_str("+[]").unwrap();
+        let config = ExploreConfig::default().mem_size(1);
+        let mut path_group = PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
+        let cancel = path_group.cancel_token();
+        let res = path_group.explore_until(&ctx, &mut solver, |state, _| {
+            if state.steps > 10 {
+                cancel.cancel();
+            }
+            ExploreFnResult::<()>::Valid
+        });
+        assert_eq!(res, Err(Error::Cancelled));
+    }
+
+    #[test]
+    fn test_generate_tests() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        // Prints whether the input byte is 0
+        let prog = Rc::new(ast::Prog::from_str(",[>+<[-]]>.").unwrap());
+        let config = ExploreConfig::default().mem_size(2);
+        let mut path_group = PathGroup::make_entry(&ctx, prog, config).unwrap();
+        let suite = path_group.generate_tests(&ctx, &mut solver).unwrap();
+        assert!(suite.complete);
+
+        let mut outputs = suite
+            .cases
+            .iter()
+            .map(|case| case.output.clone())
+            .collect::<Vec<Vec<u8>>>();
+        outputs.sort();
+        outputs.dedup();
+        assert_eq!(outputs, [[0], [1]]);
+        let skipped = suite.cases.iter().find(|case| case.output == [0]).unwrap();
+        assert_eq!(skipped.input, [0]);
+        assert_eq!(skipped.branches, [(1, true)]);
+        assert!(skipped
+            .to_json("path.bin")
+            .contains("\"input_file\":\"path.bin\""));
+    }
+
+    #[test]
+    fn test_extract_dictionary() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let mut solver = CachedSolver::new();
+
+        // Branches on whether the input byte is 90, built by a counter loop
+        let prog = Rc::new(ast::Prog::from_str(",>+++++++++[<---------->-]<[>]").unwrap());
+        let config = ExploreConfig::default().mem_size(3);
+        let mut path_group = PathGroup::make_entry(&ctx, prog, config).unwrap();
+        let dict = path_group.extract_dictionary(&ctx, &mut solver).unwrap();
+        assert!(dict.complete);
+        assert_eq!(dict.tokens, [b"Z"]);
+        assert_eq!(dict.to_afl(), "kw1=\"Z\"\n");
+    }
 }
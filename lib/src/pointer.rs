@@ -0,0 +1,164 @@
+use crate::ast;
+
+use std::ops::RangeInclusive;
+
+/// Static data pointer offsets of a program, relative to where the pointer
+/// starts. A loop is balanced if its body leaves the pointer where it found
+/// it, in which case every iteration sees the same offsets. The pointer
+/// position after an unbalanced loop depends on how many times it ran, so
+/// offsets are unknown from then on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointerAnalysis {
+    /// Offset before executing each instruction, plus the offset at exit
+    offsets: Vec<Option<isize>>,
+
+    /// Each loop's '[' index, along with whether the loop is balanced
+    loops: Vec<(usize, bool)>,
+}
+
+impl PointerAnalysis {
+    pub fn new(prog: &ast::Prog) -> Self {
+        // Find the balanced loops. Inner loops close before outer ones, so an
+        // inner loop is always decided before its enclosing loop.
+        let mut loops = vec![];
+        let mut stack: Vec<(usize, isize, bool)> = vec![];
+        for (insn_ptr, insn) in prog.0.iter().enumerate() {
+            match insn {
+                ast::Insn::JmpIfZero(_) => stack.push((insn_ptr, 0, true)),
+                ast::Insn::JmpIfNonZero(_) => {
+                    if let Some((open, net, inner_balanced)) = stack.pop() {
+                        let balanced = inner_balanced && net == 0;
+                        loops.push((open, balanced));
+                        if let Some(parent) = stack.last_mut() {
+                            parent.2 &= balanced;
+                        }
+                    }
+                }
+                ast::Insn::Right => {
+                    if let Some(top) = stack.last_mut() {
+                        top.1 += 1;
+                    }
+                }
+                ast::Insn::Left => {
+                    if let Some(top) = stack.last_mut() {
+                        top.1 -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        loops.sort_unstable();
+
+        let mut offsets = Vec::with_capacity(prog.0.len() + 1);
+        let mut offset = Some(0);
+        for (insn_ptr, insn) in prog.0.iter().enumerate() {
+            offsets.push(offset);
+            match insn {
+                ast::Insn::Right => offset = offset.map(|offset| offset + 1),
+                ast::Insn::Left => offset = offset.map(|offset| offset - 1),
+                ast::Insn::JmpIfZero(_) => {
+                    if let Ok(idx) = loops.binary_search_by_key(&insn_ptr, |&(open, _)| open) {
+                        if !loops[idx].1 {
+                            offset = None;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        offsets.push(offset);
+
+        Self { offsets, loops }
+    }
+
+    /// Data pointer offset before executing the instruction at `insn_ptr`, or
+    /// at exit if `insn_ptr` is the program length. This is `None` if the
+    /// offset depends on how many times an unbalanced loop ran.
+    pub fn offset(&self, insn_ptr: usize) -> Option<isize> {
+        self.offsets.get(insn_ptr).cloned().flatten()
+    }
+
+    /// Whether the loop whose '[' is at `open` leaves the pointer where it
+    /// found it, or `None` if there is no '[' at `open`
+    pub fn is_balanced(&self, open: usize) -> Option<bool> {
+        self.loops
+            .binary_search_by_key(&open, |&(open, _)| open)
+            .ok()
+            .map(|idx| self.loops[idx].1)
+    }
+
+    /// Indices of the '[' of every unbalanced loop
+    pub fn unbalanced_loops(&self) -> Vec<usize> {
+        self.loops
+            .iter()
+            .filter(|(_, balanced)| !balanced)
+            .map(|&(open, _)| open)
+            .collect()
+    }
+
+    /// Offsets the data pointer can reach, or `None` if some offset is unknown
+    pub fn offset_range(&self) -> Option<RangeInclusive<isize>> {
+        let offsets = self
+            .offsets
+            .iter()
+            .cloned()
+            .collect::<Option<Vec<isize>>>()?;
+        let min = offsets.iter().cloned().min()?;
+        let max = offsets.iter().cloned().max()?;
+        Some(min..=max)
+    }
+
+    /// Whether the data pointer can move left of where it started, which is a
+    /// `TapeOverflow` under `TapePolicy::Error`. This is `None` if some offset
+    /// is unknown.
+    pub fn moves_left_of_start(&self) -> Option<bool> {
+        self.offset_range().map(|range| *range.start() < 0)
+    }
+
+    /// Smallest `ExploreConfig::mem_size` where no two reachable offsets share
+    /// a cell, or `None` if that can't be bounded statically. Under
+    /// `TapePolicy::Wrap`, offsets left of the start wrap to the end of the
+    /// tape.
+    pub fn min_tape_size(&self) -> Option<usize> {
+        self.offset_range()
+            .map(|range| (range.end() - range.start()) as usize + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balanced() {
+        let prog = ast::Prog::from_str(",>,[-<+>]<.").unwrap();
+        let analysis = PointerAnalysis::new(&prog);
+        assert_eq!(analysis.is_balanced(3), Some(true));
+        assert_eq!(analysis.is_balanced(4), None);
+        assert_eq!(analysis.offset(5), Some(1));
+        assert_eq!(analysis.offset(6), Some(0));
+        assert_eq!(analysis.offset(10), Some(0));
+        assert_eq!(analysis.offset(11), Some(0));
+        assert_eq!(analysis.offset_range(), Some(0..=1));
+        assert_eq!(analysis.moves_left_of_start(), Some(false));
+        assert_eq!(analysis.min_tape_size(), Some(2));
+
+        let prog = ast::Prog::from_str("<<+[[->>+<<]>]").unwrap();
+        let analysis = PointerAnalysis::new(&prog);
+        assert_eq!(analysis.is_balanced(4), Some(true));
+        assert_eq!(analysis.min_tape_size(), None);
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        let prog = ast::Prog::from_str("+[>,]<[[-]<]>.").unwrap();
+        let analysis = PointerAnalysis::new(&prog);
+        assert_eq!(analysis.unbalanced_loops(), vec![1, 6]);
+        assert_eq!(analysis.is_balanced(7), Some(true));
+        assert_eq!(analysis.offset(1), Some(0));
+        assert_eq!(analysis.offset(2), None);
+        assert_eq!(analysis.offset(13), None);
+        assert_eq!(analysis.offset_range(), None);
+        assert_eq!(analysis.min_tape_size(), None);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::ast;
+use crate::cached_solver::CachedSolver;
+use crate::config::ExploreConfig;
+use crate::coverage::Coverage;
+use crate::dict::Dictionary;
+use crate::error::Result;
+use crate::minimize::minimize_input;
+use crate::minimize::MinimizedInput;
+use crate::observer::ExplorationObserver;
+use crate::path_group::CancelToken;
+use crate::path_group::ExploreFnResult;
+use crate::path_group::PathGroup;
+use crate::state::ConcreteState;
+use crate::state::State;
+use crate::stats::ExploreStats;
+use crate::testgen::TestSuite;
+use crate::tree::ExploreTree;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Symbolic execution session that owns its z3 context, solver, program and
+/// path group, so callers don't have to thread the `'ctx` lifetime around.
+pub struct Session {
+    // The fields borrowing `ctx` are declared before it so they are dropped
+    // first. They are given a `'static` lifetime internally, but are only ever
+    // exposed through closures that are generic over the context lifetime, so
+    // nothing borrowing the context can outlive the session.
+    path_group: PathGroup<'static>,
+    solver: CachedSolver<'static>,
+    prog: Rc<ast::Prog>,
+    ctx: Box<z3::Context>,
+}
+
+impl Session {
+    pub fn new(prog: ast::Prog, config: ExploreConfig) -> Result<Self> {
+        let ctx = Box::new(z3::Context::new(&z3::Config::new()));
+        let prog = Rc::new(prog);
+        // SAFETY: `ctx` is moved into the session along with the path group
+        let path_group =
+            PathGroup::make_entry(unsafe { Self::erase(&ctx) }, Rc::clone(&prog), config)?;
+        Ok(Self {
+            path_group,
+            solver: CachedSolver::new(),
+            prog,
+            ctx,
+        })
+    }
+
+    pub fn from_str(s: &str, config: ExploreConfig) -> Result<Self> {
+        Self::new(ast::Prog::from_str(s)?, config)
+    }
+
+    /// Extend the context borrow to `'static`.
+    ///
+    /// # Safety
+    ///
+    /// `ctx` must be the session's boxed context, whose address is stable
+    /// until the session is dropped. The result may only be stored in the
+    /// session's own fields, which are dropped before the context, or passed
+    /// to closures generic over the context lifetime.
+    unsafe fn erase(ctx: &z3::Context) -> &'static z3::Context {
+        &*(ctx as *const z3::Context)
+    }
+
+    pub fn prog(&self) -> &ast::Prog {
+        &self.prog
+    }
+
+    pub fn config(&self) -> &ExploreConfig {
+        self.path_group.config()
+    }
+
+    pub fn cancel_token(&self) -> CancelToken {
+        self.path_group.cancel_token()
+    }
+
+    pub fn stats(&self) -> &ExploreStats {
+        self.path_group.stats()
+    }
+
+    /// Instructions and branch directions reached so far
+    pub fn coverage(&self) -> &Coverage {
+        self.path_group.coverage()
+    }
+
+    /// Exploration tree in Graphviz DOT format, if
+    /// `ExploreConfig::record_tree` is set
+    pub fn tree_dot(&self) -> Option<String> {
+        self.path_group.tree().map(ExploreTree::to_dot)
+    }
+
+    /// Exploration tree as JSON, if `ExploreConfig::record_tree` is set
+    pub fn tree_json(&self) -> Option<String> {
+        self.path_group.tree().map(ExploreTree::to_json)
+    }
+
+    /// Notify `observer` of exploration events. The observer can't borrow
+    /// from the session's context, so it must implement
+    /// `ExplorationObserver` for every context lifetime.
+    pub fn add_observer<O>(&mut self, observer: Rc<RefCell<O>>)
+    where
+        O: for<'ctx> ExplorationObserver<'ctx> + 'static,
+    {
+        self.path_group.add_observer(observer);
+    }
+
+    /// Call `callback` with the exploration stats at most once every
+    /// `interval` while exploring
+    pub fn set_progress<F>(&mut self, interval: Duration, callback: F)
+    where
+        F: FnMut(&ExploreStats) + 'static,
+    {
+        self.path_group.set_progress(interval, callback);
+    }
+
+    /// Discard explored paths and restart from the entry state. The solver
+    /// cache and the cancel token are kept, and the token is cleared.
+    pub fn reset(&mut self) -> Result<()> {
+        let config = self.config().clone();
+        let cancel = self.cancel_token();
+        // SAFETY: the path group is stored in the session that owns `ctx`
+        let ctx = unsafe { Self::erase(&self.ctx) };
+        self.path_group = PathGroup::make_entry(ctx, Rc::clone(&self.prog), config)?;
+        self.path_group.set_cancel_token(cancel);
+        Ok(())
+    }
+
+    /// Run a closure with access to the context, path group, and solver of
+    /// this session. The closure must work for any context lifetime, which
+    /// prevents values borrowing the context from escaping it.
+    pub fn with<F, R>(&mut self, fcn: F) -> R
+    where
+        F: for<'ctx> FnOnce(&'ctx z3::Context, &mut PathGroup<'ctx>, &mut CachedSolver<'ctx>) -> R,
+    {
+        // SAFETY: `fcn` is generic over the context lifetime, so it can't
+        // keep anything borrowing the context past this call
+        let ctx = unsafe { Self::erase(&self.ctx) };
+        fcn(ctx, &mut self.path_group, &mut self.solver)
+    }
+
+    pub fn explore_until<F, T>(&mut self, fcn: F) -> Result<Option<T>>
+    where
+        F: for<'ctx> FnMut(&State<'ctx>, &mut CachedSolver<'ctx>) -> ExploreFnResult<T>,
+    {
+        self.with(|ctx, path_group, solver| path_group.explore_until(ctx, solver, fcn))
+    }
+
+    /// Find an input reaching the instruction at `insn_ptr`. See
+    /// `PathGroup::reach()`.
+    pub fn reach(&mut self, insn_ptr: usize) -> Result<Option<ConcreteState>> {
+        self.with(|ctx, path_group, solver| path_group.reach(ctx, solver, insn_ptr))
+    }
+
+    /// Make a test case for every exited path. See
+    /// `PathGroup::generate_tests()`.
+    pub fn generate_tests(&mut self) -> Result<TestSuite> {
+        self.with(|ctx, path_group, solver| path_group.generate_tests(ctx, solver))
+    }
+
+    /// Collect a fuzzer dictionary from path conditions. See
+    /// `PathGroup::extract_dictionary()`.
+    pub fn extract_dictionary(&mut self) -> Result<Dictionary> {
+        self.with(|ctx, path_group, solver| path_group.extract_dictionary(ctx, solver))
+    }
+
+    pub fn explore_until_output(&mut self, output: &[u8]) -> Result<Option<ConcreteState>> {
+        self.with(|ctx, path_group, solver| path_group.explore_until_output(ctx, solver, output))
+    }
+
+    /// Shrink the input of a solution writing `output`. See
+    /// `minimize_input()`.
+    pub fn minimize_input(
+        &mut self,
+        output: &[u8],
+        solution: &ConcreteState,
+    ) -> Result<MinimizedInput> {
+        let prog = Rc::clone(&self.prog);
+        self.with(|ctx, path_group, solver| {
+            minimize_input(ctx, solver, prog, path_group.config(), output, solution)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        let config = ExploreConfig::default().mem_size(16);
+        let mut session = Session::from_str(",>,[-<+>]<.", config).unwrap();
+        let res = session.explore_until_output(&[2]).unwrap().unwrap();
+        assert_eq!(res.input.iter().sum::<u8>(), 2);
+    }
+
+    #[test]
+    fn test_reset() {
+        let config = ExploreConfig::default().mem_size(16);
+        let mut session = Session::from_str("+[>,]+[<.-]", config).unwrap();
+        let res = session.explore_until_output(b"AB").unwrap().unwrap();
+        assert_eq!(res.input, b"BA\x00");
+
+        session.reset().unwrap();
+        let res = session.explore_until_output(b"ABC").unwrap().unwrap();
+        assert_eq!(res.input, b"CBA\x00");
+    }
+}
@@ -1,7 +1,15 @@
 use crate::ast;
 use crate::cached_solver::CachedSolver;
-use crate::cached_solver::SolverResult;
-
+use crate::config::EofBehavior;
+use crate::config::ExploreConfig;
+use crate::error::Error;
+use crate::error::Result;
+use crate::interval::Interval;
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::iter;
 use std::rc::Rc;
 
@@ -11,24 +19,76 @@ use z3::ast::Ast as Z3Ast;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::TapePolicy;
 
     #[test]
     fn test_state_clone_eq() {
         let cfg = z3::Config::new();
         let ctx = z3::Context::new(&cfg);
         let prog = ast::Prog::from_str(",>,[-<+>]<.").unwrap();
-        let state = State::make_entry(&ctx, Rc::new(prog), 16);
+        let config = ExploreConfig::default().mem_size(16);
+        let state = State::make_entry(&ctx, Rc::new(prog), Rc::new(config));
         assert_eq!(state, state);
         assert_eq!(state.clone(), state);
         assert_eq!(state.clone(), state.clone());
     }
+
+    #[test]
+    fn test_empty_tape() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let prog = ast::Prog::from_str("+").unwrap();
+        let config = ExploreConfig::default().mem_size(0);
+        let state = State::make_entry(&ctx, Rc::new(prog), Rc::new(config));
+        assert_eq!(state.step(&ctx), Err(Error::TapeOverflow { insn_ptr: 0 }));
+    }
+
+    #[test]
+    fn test_tape_policy() {
+        let cfg = z3::Config::new();
+        let ctx = z3::Context::new(&cfg);
+        let prog = Rc::new(ast::Prog::from_str("<").unwrap());
+
+        let config = ExploreConfig::default().mem_size(4);
+        let state = State::make_entry(&ctx, Rc::clone(&prog), Rc::new(config.clone()));
+        assert_eq!(state.step(&ctx).unwrap()[0].data_ptr, 3);
+
+        let config = config.tape_policy(TapePolicy::Error);
+        let state = State::make_entry(&ctx, prog, Rc::new(config));
+        assert_eq!(state.step(&ctx), Err(Error::TapeOverflow { insn_ptr: 0 }));
+    }
+
+    #[test]
+    fn test_is_if_body() {
+        let prog = ast::Prog::from_str("[[-]>+<]").unwrap();
+        let body = &prog.0[1..prog.0.len() - 1];
+        assert!(is_if_body(body, 2));
+        // With one cell, '>+<' sets the cell that was just cleared
+        assert!(!is_if_body(body, 1));
+    }
+}
+
+/// How `State::step_with_kind()` executed the current instruction
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum StepKind {
+    /// Executed it normally, forking at a branch that can go either way
+    Executed,
+
+    /// Took the only direction of a branch that cell intervals allow
+    Decided,
+
+    /// Ran the loop starting at the current '[' as an if statement
+    Veritested,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Default, Hash)]
 pub struct SymBytes<'ctx>(pub Vec<z3::ast::BV<'ctx>>);
 
-/// Symbolic program state
-#[derive(Clone, Setters, PartialEq, Eq, Debug, Hash)]
+/// Symbolic program state. Equality and hashing ignore the bookkeeping fields
+/// `steps`, `id`, `parent_id`, `prev_insn_ptr`, `intervals` and `merged`, so
+/// states reached along different paths compare equal if they execute the
+/// same way.
+#[derive(Clone, Setters, Debug)]
 pub struct State<'ctx> {
     /// Brainf*** program
     pub prog: Rc<ast::Prog>,
@@ -51,13 +111,66 @@ pub struct State<'ctx> {
     /// Constraints required for this state to be valid. This is a collection of
     /// all the conditions that cause the program to branch to this state.
     pub path: z3::ast::Bool<'ctx>,
+
+    /// Execution options
+    pub config: Rc<ExploreConfig>,
+
+    /// Number of instructions executed to reach this state. A merged or
+    /// veritested state stands for several paths, and has the largest count.
+    pub steps: usize,
+
+    /// Id of the path this state is on. States keep the id of the state they
+    /// were stepped from, unless the step forked.
+    pub id: usize,
+
+    /// Id of the path this state's path forked from
+    pub parent_id: Option<usize>,
+
+    /// Instruction executed to reach this state
+    pub prev_insn_ptr: Option<usize>,
+
+    /// Range of values each memory cell can hold
+    pub intervals: Vec<Interval>,
+
+    /// Whether this state, or one it was stepped from, was merged or
+    /// veritested, so it stands for several instruction sequences
+    pub merged: bool,
+}
+
+impl<'ctx> PartialEq for State<'ctx> {
+    fn eq(&self, other: &Self) -> bool {
+        self.prog == other.prog
+            && self.mem == other.mem
+            && self.insn_ptr == other.insn_ptr
+            && self.data_ptr == other.data_ptr
+            && self.input == other.input
+            && self.output == other.output
+            && self.path == other.path
+            && self.config == other.config
+    }
+}
+
+impl<'ctx> Eq for State<'ctx> {}
+
+impl<'ctx> Hash for State<'ctx> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.prog.hash(state);
+        self.mem.hash(state);
+        self.insn_ptr.hash(state);
+        self.data_ptr.hash(state);
+        self.input.hash(state);
+        self.output.hash(state);
+        self.path.hash(state);
+        self.config.hash(state);
+    }
 }
 
 /// Concrete program state
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConcreteState {
-    /// Memory bytes
-    pub mem: Vec<u8>,
+    /// Memory cells. These are `u64` so they can hold any
+    /// `ExploreConfig::cell_bits`.
+    pub mem: Vec<u64>,
 
     /// Instruction pointer
     pub insn_ptr: usize,
@@ -72,35 +185,176 @@ pub struct ConcreteState {
     pub output: Vec<u8>,
 }
 
-fn init_mem(ctx: &z3::Context, mem_size: usize) -> SymBytes {
-    let zero = z3::ast::BV::from_u64(ctx, 0, 8);
-    SymBytes(iter::repeat(zero).take(mem_size).collect())
+/// Whether `body`, the inside of a loop, runs at most once and has no loops
+/// of its own other than clear loops like `[-]`. That is the case when it
+/// ends at the cell it started on, after zeroing it with a clear loop, and it
+/// does no I/O. Offsets are only distinct cells if they span fewer than
+/// `mem_size` cells, since the tape may wrap.
+fn is_if_body(body: &[ast::Insn], mem_size: usize) -> bool {
+    let mut offset = 0isize;
+    let (mut min_offset, mut max_offset) = (0, 0);
+    let mut zeroed = BTreeSet::new();
+    let mut i = 0;
+    while i < body.len() {
+        match body[i] {
+            ast::Insn::Right => offset += 1,
+            ast::Insn::Left => offset -= 1,
+            ast::Insn::Inc | ast::Insn::Dec => {
+                zeroed.remove(&offset);
+            }
+            ast::Insn::JmpIfZero(_) => match body.get(i + 1..i + 3) {
+                Some([ast::Insn::Inc, ast::Insn::JmpIfNonZero(_)])
+                | Some([ast::Insn::Dec, ast::Insn::JmpIfNonZero(_)]) => {
+                    zeroed.insert(offset);
+                    i += 2;
+                }
+                _ => return false,
+            },
+            ast::Insn::JmpIfNonZero(_) | ast::Insn::In | ast::Insn::Out => return false,
+        }
+        min_offset = min_offset.min(offset);
+        max_offset = max_offset.max(offset);
+        i += 1;
+    }
+    offset == 0 && zeroed.contains(&0) && max_offset.abs_diff(min_offset) < mem_size
+}
+
+fn init_mem<'ctx>(ctx: &'ctx z3::Context, config: &ExploreConfig) -> SymBytes<'ctx> {
+    let zero = z3::ast::BV::from_u64(ctx, 0, config.cell_bits);
+    SymBytes(iter::repeat(zero).take(config.mem_size).collect())
 }
 
 impl<'ctx> State<'ctx> {
-    pub fn make_entry(ctx: &'ctx z3::Context, prog: Rc<ast::Prog>, mem_size: usize) -> Self {
+    pub fn make_entry(
+        ctx: &'ctx z3::Context,
+        prog: Rc<ast::Prog>,
+        config: Rc<ExploreConfig>,
+    ) -> Self {
         State {
             prog,
-            mem: init_mem(ctx, mem_size),
+            mem: init_mem(ctx, &config),
+            intervals: vec![Interval::constant(0); config.mem_size],
             insn_ptr: 0,
             data_ptr: 0,
             input: SymBytes::default(),
             output: SymBytes::default(),
             path: z3::ast::Bool::from_bool(ctx, true),
+            config,
+            steps: 0,
+            id: 0,
+            parent_id: None,
+            prev_insn_ptr: None,
+            merged: false,
         }
     }
 
-    pub fn step(&self, ctx: &'ctx z3::Context) -> Vec<Self> {
-        match self.prog.0.get(self.insn_ptr) {
-            Some(ast::Insn::Right) => vec![self.op_right()],
-            Some(ast::Insn::Left) => vec![self.op_left()],
-            Some(ast::Insn::Inc) => vec![self.op_inc(ctx)],
-            Some(ast::Insn::Dec) => vec![self.op_dec(ctx)],
-            Some(ast::Insn::Out) => vec![self.op_out()],
-            Some(ast::Insn::In) => vec![self.op_in(ctx)],
-            Some(ast::Insn::JmpIfZero(insn_ptr)) => self.op_jmp_if_zero(ctx, *insn_ptr),
-            Some(ast::Insn::JmpIfNonZero(insn_ptr)) => self.op_jmp_if_non_zero(ctx, *insn_ptr),
-            None => vec![],
+    pub fn step(&self, ctx: &'ctx z3::Context) -> Result<Vec<Self>> {
+        self.step_with_kind(ctx).map(|(states, _)| states)
+    }
+
+    /// Like `step()`, also returning how the instruction was executed
+    pub fn step_with_kind(&self, ctx: &'ctx z3::Context) -> Result<(Vec<Self>, StepKind)> {
+        let (states, kind) = match self.prog.0.get(self.insn_ptr) {
+            Some(ast::Insn::Right) => (vec![self.op_right()?], StepKind::Executed),
+            Some(ast::Insn::Left) => (vec![self.op_left()?], StepKind::Executed),
+            Some(ast::Insn::Inc) => (vec![self.op_inc(ctx)?], StepKind::Executed),
+            Some(ast::Insn::Dec) => (vec![self.op_dec(ctx)?], StepKind::Executed),
+            Some(ast::Insn::Out) => (vec![self.op_out()?], StepKind::Executed),
+            Some(ast::Insn::In) => (vec![self.op_in(ctx)?], StepKind::Executed),
+            Some(ast::Insn::JmpIfZero(insn_ptr)) => self.op_jmp_if_zero(ctx, *insn_ptr)?,
+            Some(ast::Insn::JmpIfNonZero(insn_ptr)) => self.op_jmp_if_non_zero(ctx, *insn_ptr)?,
+            None => (vec![], StepKind::Executed),
+        };
+        let states = states
+            .into_iter()
+            .map(|state| {
+                let steps = state.steps + 1;
+                state.steps(steps).prev_insn_ptr(Some(self.insn_ptr))
+            })
+            .collect();
+        Ok((states, kind))
+    }
+
+    /// Whether `other` is at the same point of execution as this state, so
+    /// the two can be merged. Their memory, output, and path may differ.
+    pub fn can_merge(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.prog, &other.prog)
+            && self.insn_ptr == other.insn_ptr
+            && self.data_ptr == other.data_ptr
+            && self.mem.0.len() == other.mem.0.len()
+            && self.input.0.len() == other.input.0.len()
+            && self.output.0.len() == other.output.0.len()
+    }
+
+    /// Number of memory cells and output bytes that differ between this state
+    /// and `other`, which must satisfy `can_merge()`
+    pub fn differing_cells(&self, other: &Self) -> usize {
+        let mem = self.mem.0.iter().zip(&other.mem.0);
+        let output = self.output.0.iter().zip(&other.output.0);
+        mem.chain(output).filter(|(a, b)| a != b).count()
+    }
+
+    /// Single state standing for both this state and `other`, which must
+    /// satisfy `can_merge()`. Values that differ become `ite` expressions
+    /// choosing between the two on this state's path condition. Input bytes
+    /// are named by their index, so they are already shared.
+    pub fn merge(&self, other: &Self) -> Self {
+        let merge_syms = |a: &SymBytes<'ctx>, b: &SymBytes<'ctx>| {
+            SymBytes(
+                a.0.iter()
+                    .zip(&b.0)
+                    .map(|(a, b)| {
+                        if a == b {
+                            a.clone()
+                        } else {
+                            self.path.ite(a, b).simplify()
+                        }
+                    })
+                    .collect(),
+            )
+        };
+        let intervals = self
+            .intervals
+            .iter()
+            .zip(&other.intervals)
+            .map(|(a, b)| a.join(b))
+            .collect();
+        self.clone()
+            .mem(merge_syms(&self.mem, &other.mem))
+            .output(merge_syms(&self.output, &other.output))
+            .path(self.path.or(&[&other.path]).simplify())
+            .intervals(intervals)
+            .steps(self.steps.max(other.steps))
+            .merged(true)
+    }
+
+    /// Whether cell intervals rule out a direction of the branch at the
+    /// current instruction
+    pub fn branch_decided(&self) -> bool {
+        self.config.intervals
+            && self
+                .get_interval()
+                .map(|interval| !interval.contains_zero() || !interval.contains_non_zero())
+                .unwrap_or(false)
+    }
+
+    /// Condition on the current cell for execution to continue at `insn_ptr`,
+    /// or `None` if the current instruction isn't a branch
+    pub fn branch_condition(
+        &self,
+        ctx: &'ctx z3::Context,
+        insn_ptr: usize,
+    ) -> Option<z3::ast::Bool<'ctx>> {
+        let (target, if_zero) = match self.prog.0.get(self.insn_ptr)? {
+            ast::Insn::JmpIfZero(target) => (*target, true),
+            ast::Insn::JmpIfNonZero(target) => (*target, false),
+            _ => return None,
+        };
+        let cell_eq_zero = self.get_cell().ok()?._eq(&self.cell_const(ctx, 0));
+        if (insn_ptr == target) == if_zero {
+            Some(cell_eq_zero)
+        } else {
+            Some(cell_eq_zero.not())
         }
     }
 
@@ -112,7 +366,7 @@ impl<'ctx> State<'ctx> {
         &self,
         ctx: &'ctx z3::Context,
         solver: &mut CachedSolver<'ctx>,
-    ) -> SolverResult<ConcreteState> {
+    ) -> Result<ConcreteState> {
         self.concretize_helper(ctx, solver, None)
     }
 
@@ -121,7 +375,7 @@ impl<'ctx> State<'ctx> {
         ctx: &'ctx z3::Context,
         solver: &mut CachedSolver<'ctx>,
         constraint: &z3::ast::Bool<'ctx>,
-    ) -> SolverResult<ConcreteState> {
+    ) -> Result<ConcreteState> {
         self.concretize_helper(ctx, solver, Some(constraint))
     }
 
@@ -130,91 +384,152 @@ impl<'ctx> State<'ctx> {
         ctx: &'ctx z3::Context,
         solver: &mut CachedSolver<'ctx>,
         constraint: Option<&z3::ast::Bool<'ctx>>,
-    ) -> SolverResult<ConcreteState> {
+    ) -> Result<ConcreteState> {
         let expr = match constraint {
             Some(constraint) => z3::ast::Bool::and(&self.path, &[&constraint]),
             None => self.path.clone(),
         };
-        match solver.solve(ctx, expr) {
-            Ok(model) => {
-                Ok(ConcreteState::from_model(&model, self).expect("failed concretizing state"))
-            }
-            Err(err) => Err(err),
+        let model = solver.solve_within(ctx, expr, self.config.solver_timeout)?;
+        ConcreteState::from_model(&model, self).ok_or(Error::ModelCompletion)
+    }
+
+    fn tape_overflow(&self) -> Error {
+        Error::TapeOverflow {
+            insn_ptr: self.insn_ptr,
+        }
+    }
+
+    fn cell_const(&self, ctx: &'ctx z3::Context, val: u64) -> z3::ast::BV<'ctx> {
+        z3::ast::BV::from_u64(ctx, val, self.config.cell_bits)
+    }
+
+    /// Zero-extend or truncate an input byte to the cell width
+    fn byte_to_cell(&self, byte: z3::ast::BV<'ctx>) -> z3::ast::BV<'ctx> {
+        let bits = self.config.cell_bits;
+        match bits {
+            8 => byte,
+            _ if bits > 8 => byte.zero_ext(bits - 8),
+            _ => byte.extract(bits - 1, 0),
         }
     }
 
-    fn get_cell(&self) -> z3::ast::BV<'ctx> {
-        self.mem.0[self.data_ptr].clone()
+    /// Zero-extend or truncate a cell to an output byte
+    fn cell_to_byte(&self, cell: z3::ast::BV<'ctx>) -> z3::ast::BV<'ctx> {
+        let bits = self.config.cell_bits;
+        match bits {
+            8 => cell,
+            _ if bits > 8 => cell.extract(7, 0).simplify(),
+            _ => cell.zero_ext(8 - bits).simplify(),
+        }
+    }
+
+    fn get_cell(&self) -> Result<z3::ast::BV<'ctx>> {
+        self.mem
+            .0
+            .get(self.data_ptr)
+            .cloned()
+            .ok_or_else(|| self.tape_overflow())
+    }
+
+    fn get_interval(&self) -> Result<Interval> {
+        self.intervals
+            .get(self.data_ptr)
+            .cloned()
+            .ok_or_else(|| self.tape_overflow())
     }
 
-    fn set_cell(&self, val: z3::ast::BV<'ctx>) -> Self {
+    fn set_cell(&self, val: z3::ast::BV<'ctx>, interval: Interval) -> Result<Self> {
         let val = val.simplify();
         let mem = {
             let mut mem = self.mem.clone();
-            mem.0[self.data_ptr] = val;
+            *mem.0
+                .get_mut(self.data_ptr)
+                .ok_or_else(|| self.tape_overflow())? = val;
             mem
         };
-        self.clone().mem(mem)
+        Ok(self.clone().mem(mem).set_interval(interval))
+    }
+
+    fn set_interval(mut self, interval: Interval) -> Self {
+        if let Some(cell) = self.intervals.get_mut(self.data_ptr) {
+            *cell = interval;
+        }
+        self
     }
 
     fn inc_insn_ptr(&self) -> Self {
         self.clone().insn_ptr(self.insn_ptr + 1)
     }
 
-    fn op_right(&self) -> Self {
-        self.clone()
-            .data_ptr((self.data_ptr + 1) % self.mem.0.len())
-            .inc_insn_ptr()
+    fn op_right(&self) -> Result<Self> {
+        let data_ptr = self
+            .config
+            .ptr_right(self.data_ptr)
+            .ok_or_else(|| self.tape_overflow())?;
+        Ok(self.clone().data_ptr(data_ptr).inc_insn_ptr())
     }
 
-    fn op_left(&self) -> Self {
-        self.clone()
-            .data_ptr(
-                // TODO: Make this clearer
-                self.data_ptr
-                    .checked_sub(1)
-                    .unwrap_or(self.mem.0.len().checked_sub(1).unwrap_or(0)),
-            )
-            .inc_insn_ptr()
+    fn op_left(&self) -> Result<Self> {
+        let data_ptr = self
+            .config
+            .ptr_left(self.data_ptr)
+            .ok_or_else(|| self.tape_overflow())?;
+        Ok(self.clone().data_ptr(data_ptr).inc_insn_ptr())
     }
 
-    fn op_inc_dec_helper(&self, ctx: &'ctx z3::Context, is_inc: bool) -> Self {
-        let one = z3::ast::BV::from_u64(ctx, 1, 8);
+    fn op_inc_dec_helper(&self, ctx: &'ctx z3::Context, is_inc: bool) -> Result<Self> {
+        let one = self.cell_const(ctx, 1);
         let fcn = if is_inc {
             z3::ast::BV::bvadd
         } else {
             z3::ast::BV::bvsub
         };
-        let old_val = self.get_cell();
+        let old_val = self.get_cell()?;
         let new_val = fcn(&old_val, &one);
-        self.set_cell(new_val).inc_insn_ptr()
+        let mask = self.config.cell_mask();
+        let interval = self.get_interval()?;
+        let interval = if is_inc {
+            interval.inc(mask)
+        } else {
+            interval.dec(mask)
+        };
+        Ok(self.set_cell(new_val, interval)?.inc_insn_ptr())
     }
 
-    fn op_inc(&self, ctx: &'ctx z3::Context) -> Self {
+    fn op_inc(&self, ctx: &'ctx z3::Context) -> Result<Self> {
         self.op_inc_dec_helper(ctx, true)
     }
 
-    fn op_dec(&self, ctx: &'ctx z3::Context) -> Self {
+    fn op_dec(&self, ctx: &'ctx z3::Context) -> Result<Self> {
         self.op_inc_dec_helper(ctx, false)
     }
 
-    fn op_out(&self) -> Self {
-        self.clone()
+    fn op_out(&self) -> Result<Self> {
+        Ok(self
+            .clone()
             .output(SymBytes(
                 self.output
                     .0
                     .clone()
                     .into_iter()
-                    .chain(iter::once(self.get_cell()))
+                    .chain(iter::once(self.cell_to_byte(self.get_cell()?)))
                     .collect(),
             ))
-            .inc_insn_ptr()
+            .inc_insn_ptr())
     }
 
-    fn op_in(&self, ctx: &'ctx z3::Context) -> Self {
+    fn op_in(&self, ctx: &'ctx z3::Context) -> Result<Self> {
+        if Some(self.input.0.len()) == self.config.max_input_len {
+            return self.op_in_eof(ctx);
+        }
         let name = format!("input[{}]", self.input.0.len());
         let val = z3::ast::BV::new_const(ctx, name, 8);
-        self.clone()
+        let path = match self.input_range_constraint(ctx, &val) {
+            Some(in_range) => self.path.and(&[&in_range]).simplify(),
+            None => self.path.clone(),
+        };
+        Ok(self
+            .clone()
             .input(SymBytes(
                 self.input
                     .0
@@ -223,51 +538,231 @@ impl<'ctx> State<'ctx> {
                     .chain(iter::once(val.clone()))
                     .collect(),
             ))
-            .set_cell(val)
-            .inc_insn_ptr()
+            .path(path)
+            .set_cell(self.byte_to_cell(val), self.input_interval())?
+            .inc_insn_ptr())
     }
 
-    fn op_jmp_helper(&self, ctx: &'ctx z3::Context, insn_ptr: usize, if_zero: bool) -> Vec<Self> {
-        let cell_eq_zero = self.get_cell()._eq(&z3::ast::BV::from_u64(ctx, 0, 8));
+    fn op_in_eof(&self, ctx: &'ctx z3::Context) -> Result<Self> {
+        let mask = self.config.cell_mask();
+        let (val, interval) = match self.config.eof {
+            EofBehavior::Zero => (self.cell_const(ctx, 0), Interval::constant(0)),
+            EofBehavior::AllOnes => (self.cell_const(ctx, mask), Interval::constant(mask)),
+            EofBehavior::Unchanged => (self.get_cell()?, self.get_interval()?),
+        };
+        Ok(self.set_cell(val, interval)?.inc_insn_ptr())
+    }
+
+    /// Range of cell values an input byte can produce
+    fn input_interval(&self) -> Interval {
+        let lo = *self.config.input_range.start() as u64;
+        let hi = *self.config.input_range.end() as u64;
+        let mask = self.config.cell_mask();
+        if hi <= mask {
+            Interval { lo, hi }
+        } else {
+            Interval::full(mask)
+        }
+    }
+
+    /// Constraint keeping an input byte within `ExploreConfig::input_range`, or
+    /// `None` if every byte is allowed
+    fn input_range_constraint(
+        &self,
+        ctx: &'ctx z3::Context,
+        byte: &z3::ast::BV<'ctx>,
+    ) -> Option<z3::ast::Bool<'ctx>> {
+        let (lo, hi) = (
+            *self.config.input_range.start(),
+            *self.config.input_range.end(),
+        );
+        if lo == u8::MIN && hi == u8::MAX {
+            return None;
+        }
+        let lo = z3::ast::BV::from_u64(ctx, lo as u64, 8);
+        let hi = z3::ast::BV::from_u64(ctx, hi as u64, 8);
+        Some(byte.bvuge(&lo).and(&[&byte.bvule(&hi)]))
+    }
+
+    fn op_jmp_helper(
+        &self,
+        ctx: &'ctx z3::Context,
+        insn_ptr: usize,
+        if_zero: bool,
+    ) -> Result<(Vec<Self>, StepKind)> {
+        let cell_eq_zero = self.get_cell()?._eq(&self.cell_const(ctx, 0));
         let cell_not_eq_zero = z3::ast::Bool::not(&cell_eq_zero);
 
-        let zero_path = self.path.and(&[&cell_eq_zero]).simplify();
-        let non_zero_path = self.path.and(&[&cell_not_eq_zero]).simplify();
+        // Drop a direction the cell's interval rules out, and don't add a
+        // condition the interval already implies to the path
+        let interval = self.get_interval()?;
+        let can_be_zero = !self.config.intervals || interval.contains_zero();
+        let can_be_non_zero = !self.config.intervals || interval.contains_non_zero();
+        let branch_path = |cond: &z3::ast::Bool<'ctx>| {
+            if can_be_zero && can_be_non_zero {
+                self.path.and(&[cond]).simplify()
+            } else {
+                self.path.clone()
+            }
+        };
+        let zero =
+            Some((branch_path(&cell_eq_zero), Interval::constant(0))).filter(|_| can_be_zero);
+        let non_zero =
+            Some((branch_path(&cell_not_eq_zero), interval.non_zero())).filter(|_| can_be_non_zero);
+
+        if if_zero && can_be_zero && can_be_non_zero {
+            if let Some(state) = self.veritest(ctx, insn_ptr) {
+                return Ok((vec![state], StepKind::Veritested));
+            }
+        }
+        let kind = if can_be_zero && can_be_non_zero {
+            StepKind::Executed
+        } else {
+            StepKind::Decided
+        };
 
-        let (taken_path, not_taken_path) = if if_zero {
-            (zero_path, non_zero_path)
+        let (taken, not_taken) = if if_zero {
+            (zero, non_zero)
         } else {
-            (non_zero_path, zero_path)
+            (non_zero, zero)
         };
 
-        let taken = self.clone().insn_ptr(insn_ptr).path(taken_path);
-        let not_taken = self.inc_insn_ptr().path(not_taken_path);
+        let taken = taken.map(|(path, interval)| {
+            self.clone()
+                .insn_ptr(insn_ptr)
+                .path(path)
+                .set_interval(interval)
+        });
+        let not_taken =
+            not_taken.map(|(path, interval)| self.inc_insn_ptr().path(path).set_interval(interval));
 
-        vec![taken, not_taken]
+        Ok((taken.into_iter().chain(not_taken).collect(), kind))
     }
 
-    fn op_jmp_if_zero(&self, ctx: &'ctx z3::Context, insn_ptr: usize) -> Vec<Self> {
+    /// Run the loop starting at the current '[' as an if statement without
+    /// forking, if its body is short enough and runs at most once. The
+    /// result is at `end`, past the loop, and each cell the body changed
+    /// holds an `ite` choosing between the body's result and the old value
+    /// on whether the loop was entered. The steps are those of entering it,
+    /// with clear loops iterating as often as their cell's interval allows,
+    /// or as often as any value allows without `ExploreConfig::intervals`.
+    fn veritest(&self, ctx: &'ctx z3::Context, end: usize) -> Option<Self> {
+        let max_len = self.config.max_veritest_len?;
+        let body = self.prog.0.get(self.insn_ptr + 1..end - 1)?;
+        if body.len() > max_len || !is_if_body(body, self.config.mem_size) {
+            return None;
+        }
+
+        // A tape overflow in the body falls back to forking, which reports
+        // the error on the path entering the loop
+        let mut entered = self
+            .clone()
+            .set_interval(self.get_interval().ok()?.non_zero());
+        // The final ']' falls through
+        let mut steps = body.len() + 1;
+        let mut insns = body.iter();
+        while let Some(insn) = insns.next() {
+            entered = match insn {
+                ast::Insn::Right => entered.op_right().ok()?,
+                ast::Insn::Left => entered.op_left().ok()?,
+                ast::Insn::Inc => entered.op_inc(ctx).ok()?,
+                ast::Insn::Dec => entered.op_dec(ctx).ok()?,
+                ast::Insn::JmpIfZero(_) => {
+                    // Each iteration after the first runs its body and ']'
+                    // again
+                    let inc = matches!(insns.next(), Some(ast::Insn::Inc));
+                    insns.next();
+                    let interval = if self.config.intervals {
+                        entered.get_interval().ok()?
+                    } else {
+                        Interval::full(self.config.cell_mask())
+                    };
+                    let iterations = match (interval.hi, inc) {
+                        (0, _) => 0,
+                        (hi, false) => hi,
+                        (_, true) => self.config.cell_mask() - interval.lo.max(1) + 1,
+                    };
+                    let iterations = usize::try_from(iterations).unwrap_or(usize::MAX);
+                    steps = steps.saturating_add(iterations.saturating_sub(1).saturating_mul(2));
+                    entered
+                        .set_cell(self.cell_const(ctx, 0), Interval::constant(0))
+                        .ok()?
+                }
+                _ => return None,
+            };
+        }
+
+        // The loop's cell is zero past the loop whether or not it was entered
+        let skipped = self
+            .set_cell(self.cell_const(ctx, 0), Interval::constant(0))
+            .ok()?;
+        let cond = self.get_cell().ok()?._eq(&self.cell_const(ctx, 0)).not();
+        let mem = SymBytes(
+            entered
+                .mem
+                .0
+                .iter()
+                .zip(&skipped.mem.0)
+                .map(|(a, b)| {
+                    if a == b {
+                        b.clone()
+                    } else {
+                        cond.ite(a, b).simplify()
+                    }
+                })
+                .collect(),
+        );
+        let intervals = entered
+            .intervals
+            .iter()
+            .zip(&skipped.intervals)
+            .map(|(a, b)| a.join(b))
+            .collect();
+        Some(
+            skipped
+                .mem(mem)
+                .intervals(intervals)
+                .insn_ptr(end)
+                .steps(self.steps.saturating_add(steps))
+                .merged(true),
+        )
+    }
+
+    fn op_jmp_if_zero(
+        &self,
+        ctx: &'ctx z3::Context,
+        insn_ptr: usize,
+    ) -> Result<(Vec<Self>, StepKind)> {
         self.op_jmp_helper(ctx, insn_ptr, true)
     }
 
-    fn op_jmp_if_non_zero(&self, ctx: &'ctx z3::Context, insn_ptr: usize) -> Vec<Self> {
+    fn op_jmp_if_non_zero(
+        &self,
+        ctx: &'ctx z3::Context,
+        insn_ptr: usize,
+    ) -> Result<(Vec<Self>, StepKind)> {
         self.op_jmp_helper(ctx, insn_ptr, false)
     }
 }
 
 impl<'ctx> SymBytes<'ctx> {
-    fn concretize(&self, model: &z3::Model) -> Option<Vec<u8>> {
+    fn concretize_cells(&self, model: &z3::Model) -> Option<Vec<u64>> {
         self.0
             .iter()
-            .map(|b| model.eval(b).and_then(|b| b.as_u64()).map(|b| b as u8))
-            .collect::<Option<Vec<u8>>>()
+            .map(|b| model.eval(b).and_then(|b| b.as_u64()))
+            .collect::<Option<Vec<u64>>>()
+    }
+
+    fn concretize(&self, model: &z3::Model) -> Option<Vec<u8>> {
+        self.concretize_cells(model)
+            .map(|cells| cells.into_iter().map(|b| b as u8).collect())
     }
 }
 
 impl ConcreteState {
     fn from_model(model: &z3::Model, state: &State) -> Option<Self> {
         Some(Self {
-            mem: state.mem.concretize(model)?,
+            mem: state.mem.concretize_cells(model)?,
             insn_ptr: state.insn_ptr,
             data_ptr: state.data_ptr,
             input: state.input.concretize(model)?,
@@ -0,0 +1,98 @@
+use std::time::Duration;
+use std::time::Instant;
+
+/// Counters kept by `CachedSolver`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct SolverStats {
+    /// Number of queries, including those answered from the cache
+    pub queries: usize,
+
+    /// Number of queries answered from the cache
+    pub cache_hits: usize,
+
+    /// Time spent in z3
+    pub solver_time: Duration,
+}
+
+/// Counters describing a path exploration
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ExploreStats {
+    /// Number of states created, including the entry state
+    pub states_created: usize,
+
+    /// Number of states popped for exploration
+    pub states_explored: usize,
+
+    /// Number of times a state had more than one continuation
+    pub forks: usize,
+
+    /// Number of states that stopped before the program exited, other than
+    /// by merging
+    pub pruned: usize,
+
+    /// Number of states where the program exited
+    pub exited: usize,
+
+    /// Number of solver queries, including those answered from the cache
+    pub solver_queries: usize,
+
+    /// Number of solver queries answered from the cache
+    pub cache_hits: usize,
+
+    /// Time spent in z3
+    pub solver_time: Duration,
+
+    /// Number of branch directions ruled out by cell intervals, each of which
+    /// would have cost a solver query
+    pub queries_avoided: usize,
+
+    /// Number of loops run as if statements without forking
+    pub regions_veritested: usize,
+
+    /// Number of states merged into a pending state
+    pub states_merged: usize,
+
+    /// Largest number of pending states
+    pub max_frontier: usize,
+
+    /// Number of instructions executed across all states
+    pub insns_executed: usize,
+}
+
+/// Callback periodically called with the exploration stats
+pub(crate) struct Progress<'ctx> {
+    pub interval: Duration,
+    pub last: Instant,
+    pub callback: Box<dyn FnMut(&ExploreStats) + 'ctx>,
+}
+
+impl SolverStats {
+    /// Number of queries that were sent to z3
+    pub fn solver_calls(&self) -> usize {
+        self.queries - self.cache_hits
+    }
+}
+
+impl ExploreStats {
+    /// Number of solver queries that were sent to z3
+    pub fn solver_calls(&self) -> usize {
+        self.solver_queries - self.cache_hits
+    }
+
+    /// Add the solver work done between two snapshots of the solver stats
+    pub(crate) fn add_solver_stats(&mut self, before: &SolverStats, after: &SolverStats) {
+        self.solver_queries += after.queries - before.queries;
+        self.cache_hits += after.cache_hits - before.cache_hits;
+        self.solver_time += after.solver_time - before.solver_time;
+    }
+}
+
+impl<'ctx> Progress<'ctx> {
+    /// Call the callback if the interval has passed since the last call
+    pub fn poll(&mut self, stats: &ExploreStats) {
+        if self.last.elapsed() >= self.interval {
+            (self.callback)(stats);
+            self.last = Instant::now();
+        }
+    }
+}
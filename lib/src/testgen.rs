@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Concrete input for one exited path, along with what it's expected to do
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TestCase {
+    /// Id of the exited state's path
+    pub path_id: usize,
+
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+
+    /// Branch directions the input takes, as the branch instruction index
+    /// along with whether the jump was taken. Each direction is listed once,
+    /// in order of instruction rather than the order they're taken in.
+    pub branches: Vec<(usize, bool)>,
+}
+
+/// Test cases found by `PathGroup::generate_tests()`
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct TestSuite {
+    pub cases: Vec<TestCase>,
+
+    /// Whether every path was explored, as opposed to a budget running out
+    pub complete: bool,
+}
+
+impl TestCase {
+    /// Sidecar metadata, naming the input file `input_file`
+    pub fn to_json(&self, input_file: &str) -> String {
+        serde_json::json!({
+            "path_id": self.path_id,
+            "input_file": input_file,
+            "output": self.output,
+            "output_text": String::from_utf8_lossy(&self.output),
+            "branches": self
+                .branches
+                .iter()
+                .map(|(insn, taken)| serde_json::json!({ "insn": insn, "taken": taken }))
+                .collect::<Vec<serde_json::Value>>(),
+        })
+        .to_string()
+    }
+}
+
+impl TestSuite {
+    /// Write each case to `dir` as `path-<id>.bin` with the raw input and
+    /// `path-<id>.json` with its metadata, creating `dir` if needed
+    pub fn write_to(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        for case in &self.cases {
+            let name = format!("path-{:06}", case.path_id);
+            let input_file = format!("{}.bin", name);
+            fs::write(dir.join(&input_file), &case.input)?;
+            fs::write(
+                dir.join(format!("{}.json", name)),
+                case.to_json(&input_file),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_to() {
+        let dir = std::env::temp_dir().join(format!("brainfsym-testgen-{}", std::process::id()));
+        let suite = TestSuite {
+            cases: vec![TestCase {
+                path_id: 3,
+                input: vec![0, 7],
+                output: b"ok".to_vec(),
+                branches: vec![(1, true), (4, false)],
+            }],
+            complete: true,
+        };
+        suite.write_to(&dir).unwrap();
+        assert_eq!(fs::read(dir.join("path-000003.bin")).unwrap(), [0, 7]);
+        let json: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.join("path-000003.json")).unwrap()).unwrap();
+        assert_eq!(json["input_file"], "path-000003.bin");
+        assert_eq!(json["output_text"], "ok");
+        assert_eq!(json["branches"][1]["taken"], false);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use crate::ast;
+use crate::path_group::TerminationReason;
+use crate::state::State;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Node of the exploration tree. Each node is a path segment between two
+/// forks, and is identified by the `State::id` of the states on it.
+#[derive(Clone, Debug)]
+pub struct TreeNode<'ctx> {
+    /// Id of the states on this path segment
+    pub id: usize,
+
+    /// Id of the path segment that forked into this one
+    pub parent_id: Option<usize>,
+
+    /// Index of the branch instruction that forked into this path segment
+    pub fork_insn: Option<usize>,
+
+    /// Branch condition on the parent's current cell that leads to this path
+    /// segment
+    pub condition: Option<z3::ast::Bool<'ctx>>,
+
+    /// Why the last state of this path segment stopped, if it did
+    pub termination: Option<TerminationReason>,
+}
+
+/// Tree of forks made by a `PathGroup`
+#[derive(Clone, Debug)]
+pub struct ExploreTree<'ctx> {
+    prog: Rc<ast::Prog>,
+    nodes: Vec<TreeNode<'ctx>>,
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<'ctx> ExploreTree<'ctx> {
+    /// Tree containing only the root node, with the id of `entry`
+    pub(crate) fn new(entry: &State<'ctx>) -> Self {
+        Self {
+            prog: Rc::clone(&entry.prog),
+            nodes: vec![TreeNode {
+                id: entry.id,
+                parent_id: None,
+                fork_insn: None,
+                condition: None,
+                termination: None,
+            }],
+        }
+    }
+
+    /// Nodes indexed by id
+    pub fn nodes(&self) -> &[TreeNode<'ctx>] {
+        &self.nodes
+    }
+
+    /// Add the path segment `id`, forked from `parent`. Ids are given out in
+    /// order, so this is the next node.
+    pub(crate) fn add_fork(
+        &mut self,
+        parent: &State<'ctx>,
+        id: usize,
+        condition: Option<z3::ast::Bool<'ctx>>,
+    ) {
+        debug_assert_eq!(id, self.nodes.len());
+        self.nodes.push(TreeNode {
+            id,
+            parent_id: Some(parent.id),
+            fork_insn: Some(parent.insn_ptr),
+            condition,
+            termination: None,
+        });
+    }
+
+    pub(crate) fn set_termination(&mut self, id: usize, reason: TerminationReason) {
+        if let Some(node) = self.nodes.get_mut(id) {
+            node.termination = Some(reason);
+        }
+    }
+
+    /// Number of path segments created by each branch instruction. The
+    /// instructions with the highest counts are the loops causing path
+    /// explosion.
+    pub fn fork_counts(&self) -> BTreeMap<usize, usize> {
+        let mut counts = BTreeMap::new();
+        for insn in self.nodes.iter().filter_map(|node| node.fork_insn) {
+            *counts.entry(insn).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn describe_insn(&self, insn_ptr: usize) -> String {
+        match self.prog.0.get(insn_ptr).map(ast::Insn::to_char) {
+            Some(c) => format!("{} '{}'", insn_ptr, c),
+            None => insn_ptr.to_string(),
+        }
+    }
+
+    /// Render the tree in Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph exploration {\n");
+        for node in &self.nodes {
+            let mut label = format!("state {}", node.id);
+            if let Some(insn) = node.fork_insn {
+                label += &format!("\\nfork at {}", dot_escape(&self.describe_insn(insn)));
+            }
+            if let Some(reason) = &node.termination {
+                label += &format!("\\n{}", dot_escape(&format!("{:?}", reason)));
+            }
+            writeln!(dot, "    {} [label=\"{}\"];", node.id, label).unwrap();
+        }
+        for node in &self.nodes {
+            if let Some(parent_id) = node.parent_id {
+                let condition = node
+                    .condition
+                    .as_ref()
+                    .map(|condition| condition.to_string())
+                    .unwrap_or_default();
+                writeln!(
+                    dot,
+                    "    {} -> {} [label=\"{}\"];",
+                    parent_id,
+                    node.id,
+                    dot_escape(&condition)
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the tree as JSON
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                serde_json::json!({
+                    "id": node.id,
+                    "parent_id": node.parent_id,
+                    "fork_insn": node.fork_insn,
+                    "condition": node.condition.as_ref().map(|condition| condition.to_string()),
+                    "termination": node.termination.as_ref().map(|reason| format!("{:?}", reason)),
+                })
+            })
+            .collect::<Vec<serde_json::Value>>();
+        serde_json::json!({
+            "nodes": nodes,
+            "fork_counts": self
+                .fork_counts()
+                .into_iter()
+                .map(|(insn, count)| serde_json::json!({ "insn": insn, "count": count }))
+                .collect::<Vec<serde_json::Value>>(),
+        })
+        .to_string()
+    }
+}
@@ -25,9 +25,11 @@ fn aodrulez_crackme() {
         "<<<<<<<<<<<>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.>.",
     ))
     .unwrap();
-    let mut path_group = brainfsym::PathGroup::make_entry(&ctx, Rc::new(prog), 64);
+    let config = brainfsym::ExploreConfig::default().mem_size(64);
+    let mut path_group = brainfsym::PathGroup::make_entry(&ctx, Rc::new(prog), config).unwrap();
     let res = path_group
         .explore_until_output(&ctx, &mut solver, b"Serial :  :) Congratulations.")
+        .unwrap()
         .unwrap();
 
     // Constraints for keygen
@@ -0,0 +1,5 @@
+#[test]
+fn difftest_brainfuck_rs() {
+    let options = brainfsym::DiffTestOptions::new(env!("CARGO_BIN_EXE_bf-reference")).cases(30);
+    assert_eq!(brainfsym::difftest(&options), Ok(None));
+}